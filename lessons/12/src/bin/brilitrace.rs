@@ -100,7 +100,10 @@ fn main() {
     });
 
     // First run canonicalize literals
-    let prog = CanonicalizeLiterals.run(prog);
+    let prog = CanonicalizeLiterals.run(prog).unwrap_or_else(|e| {
+        e.report();
+        std::process::exit(2)
+    });
 
     let bbprog: BBProgram = prog.clone().try_into().unwrap_or_else(|e| {
         report_error(e);
@@ -114,7 +117,10 @@ fn main() {
             report_error(e);
         });
 
-    let trace = trace.take();
+    let trace = trace.take().unwrap_or_else(|e| {
+        e.report();
+        std::process::exit(2)
+    });
 
     let mut prog = prog;
 
@@ -3,7 +3,7 @@ use bril_rs::{Argument, Code, EffectOps, Function, Instruction, Type, ValueOps};
 use brilirs::basic_block::NumifiedInstruction;
 use lesson_3::{LVNPass, TDCEPass};
 use std::collections::HashSet;
-use utils::{BBFunction, FunctionPass, InstrExt};
+use utils::{BBFunction, Error, FunctionPass, InstrExt};
 pub struct Trace {
     prefix: Vec<String>,
     max_len: usize,
@@ -199,7 +199,7 @@ impl Trace {
     }
 
     /// Simplify the trace using LVN and TDCE, and return the resultant instructions
-    pub fn take(self) -> Vec<Code> {
+    pub fn take(self) -> Result<Vec<Code>, Error> {
         let instrs = self.instrs;
 
         // Find all variables that are read before written, and treat them as arguments
@@ -284,13 +284,13 @@ impl Trace {
 
         let func = BBFunction::from(func);
 
-        let func = LVNPass::default().func(func);
-        let func = TDCEPass.func(func);
+        let func = LVNPass::default().func(func)?;
+        let func = TDCEPass.func(func)?;
 
         let mut instrs = Function::from(func).instrs;
 
         instrs.pop(); // Remove the print instruction
 
-        instrs
+        Ok(instrs)
     }
 }
@@ -0,0 +1,11 @@
+use lesson_3::GVNPass;
+use utils::{CanonicalizeLiterals, ToSSA, run_passes, setup_logger_from_env};
+
+fn main() {
+    setup_logger_from_env();
+    run_passes(&mut [
+        Box::new(CanonicalizeLiterals),
+        Box::new(ToSSA::default()),
+        Box::new(GVNPass::default()),
+    ]);
+}
@@ -0,0 +1,242 @@
+use crate::lvn::{LVNTable, LVNValue};
+use bril_rs::{ConstOps, EffectOps, Function, Instruction, ValueOps};
+use std::collections::HashSet;
+use utils::{
+    AnalysisManager, BBFunction, CFG, DominatorTree, Error, InstrExt, Pass, PreservedAnalyses,
+};
+
+/// Global value numbering over a program in SSA form.
+///
+/// Values are interned in the same table as LVN, but instead of resetting it at every block
+/// the dominator tree is walked, with a scope pushed for each block.
+/// A value computed in a block is therefore only reused in the blocks it dominates.
+///
+/// A `get` whose `set`s all write the same value is replaced by a copy of that value,
+/// and its `set`s are removed.
+/// Inputs arriving through back edges are not known yet when the `get` is reached,
+/// so it is only merged if those edges set it to its own value.
+#[derive(Default)]
+pub struct GVNPass {
+    table: LVNTable,
+    reachable: Vec<bool>,
+    visited: Vec<bool>,
+    /// Shadow variables of the `get`s replaced by copies, whose `set`s are removed
+    merged: HashSet<String>,
+}
+
+impl GVNPass {
+    /// Number the values of the function of a dominator tree, rewriting it in place
+    pub fn tree(&mut self, doms: &mut DominatorTree) -> Result<(), Error> {
+        self.table = LVNTable::new(&doms.cfg().func().args);
+        self.reachable = doms.cfg().reachable();
+        self.visited = vec![false; doms.len()];
+        self.merged.clear();
+
+        if !doms.is_empty() {
+            self.number(doms, 0)?;
+        }
+
+        // Drop the sets feeding the merged gets
+        for block in doms.iter_mut() {
+            let instrs = block.split_off(0);
+            block.extend(instrs.into_iter().filter(|instr| match instr {
+                Instruction::Effect {
+                    op: EffectOps::Set,
+                    args,
+                    ..
+                } => !self.merged.contains(&args[0]),
+                _ => true,
+            }));
+        }
+
+        Ok(())
+    }
+
+    /// Number the values of a block, and then of the blocks it immediately dominates
+    fn number(&mut self, doms: &mut DominatorTree, bidx: usize) -> Result<(), Error> {
+        log::info!("Numbering block {}", doms.get(bidx).label_or_default());
+        self.table.push_scope();
+
+        let inputs = self.get_inputs(doms.cfg(), bidx);
+        let block = doms.get_mut(bidx);
+        let instrs = block.split_off(0);
+        for instr in instrs {
+            log::debug!("Original: {}", instr);
+            let instr = self.instr(instr, &inputs)?;
+            log::debug!("Transformed: {}", instr);
+            block.push(instr);
+        }
+        self.visited[bidx] = true;
+
+        let exit = doms.exit();
+        let children = doms
+            .children(bidx)
+            .iter()
+            .copied()
+            .filter(|&child| child != exit)
+            .collect::<Vec<_>>();
+        for child in children {
+            self.number(doms, child)?;
+        }
+
+        self.table.pop_scope();
+        Ok(())
+    }
+
+    /// Find the `get`s of a block whose inputs all have the same value,
+    /// returning the shadow variables with the index of that value
+    fn get_inputs(&self, cfg: &CFG, bidx: usize) -> Vec<(String, usize)> {
+        let preds = cfg
+            .preds(bidx)
+            .iter()
+            .copied()
+            .filter(|&pred| self.reachable[pred])
+            .collect::<Vec<_>>();
+
+        cfg.get(bidx)
+            .iter()
+            .filter_map(|instr| match instr {
+                Instruction::Value {
+                    op: ValueOps::Get,
+                    dest,
+                    ..
+                } => Some(dest),
+                _ => None,
+            })
+            .filter_map(|shadow| {
+                let mut value = None;
+                for &pred in &preds {
+                    let arg = cfg.get(pred).iter().find_map(|instr| match instr {
+                        Instruction::Effect {
+                            op: EffectOps::Set,
+                            args,
+                            ..
+                        } if &args[0] == shadow => Some(&args[1]),
+                        _ => None,
+                    })?;
+
+                    // The get keeps its value around this edge
+                    if arg == shadow {
+                        continue;
+                    }
+
+                    if !self.visited[pred] {
+                        return None;
+                    }
+
+                    let idx = self.table.idx(arg)?;
+                    if value.is_some_and(|v| v != idx) {
+                        return None;
+                    }
+                    value = Some(idx);
+                }
+
+                value.map(|idx| (shadow.clone(), idx))
+            })
+            .collect()
+    }
+
+    /// Number a single instruction, replacing it with a copy if its value is already available
+    fn instr(
+        &mut self,
+        instr: Instruction,
+        inputs: &[(String, usize)],
+    ) -> Result<Instruction, Error> {
+        if let Instruction::Value {
+            op: ValueOps::Get,
+            dest,
+            op_type,
+            pos,
+            ..
+        } = &instr
+            && let Some(&(_, idx)) = inputs.iter().find(|(shadow, _)| shadow == dest)
+        {
+            log::debug!("Merging {} into <{}>", dest, idx);
+            self.table.alias(dest, idx);
+            self.merged.insert(dest.clone());
+
+            return Ok(match self.table.value(idx) {
+                LVNValue::Literal(l) => Instruction::Constant {
+                    dest: dest.clone(),
+                    const_type: op_type.clone(),
+                    value: l.clone().into(),
+                    op: ConstOps::Const,
+                    pos: pos.clone(),
+                },
+                _ => Instruction::Value {
+                    dest: dest.clone(),
+                    op: ValueOps::Id,
+                    args: vec![self.table.representative(idx).clone()],
+                    funcs: vec![],
+                    labels: vec![],
+                    op_type: op_type.clone(),
+                    pos: pos.clone(),
+                },
+            });
+        }
+
+        let instr = match instr {
+            Instruction::Effect {
+                op: EffectOps::Set, ..
+            } => {
+                // The shadow variable names the get being set, it is not a value.
+                // Constants are set from their local copy, since a shared representative
+                // would interfere with the get and leave a copy behind out of SSA
+                let mut args = instr.args().unwrap();
+                let literal = self
+                    .table
+                    .idx(&args[1])
+                    .is_some_and(|idx| matches!(self.table.value(idx), LVNValue::Literal(_)));
+                let mut instr = self.table.transform(instr);
+                if !literal {
+                    args[1] = instr.args().unwrap()[1].clone();
+                }
+                instr.set_args(args);
+                instr
+            }
+            _ => self.table.transform(instr),
+        };
+
+        let Some((exists, idx)) = self.table.intern(&instr)? else {
+            return Ok(instr);
+        };
+        let dest = instr.dest().unwrap();
+
+        Ok(if exists {
+            self.table.build_ref(&dest).unwrap_or(instr)
+        } else {
+            match self.table.value(idx) {
+                LVNValue::Literal(l) => Instruction::Constant {
+                    dest,
+                    const_type: instr.get_type().unwrap(),
+                    value: l.clone().into(),
+                    op: ConstOps::Const,
+                    pos: instr.get_pos(),
+                },
+                LVNValue::Op(..) | LVNValue::Unknown(_) => instr,
+            }
+        })
+    }
+}
+
+impl Pass for GVNPass {
+    fn function_with(
+        &mut self,
+        func: Function,
+        am: &mut AnalysisManager,
+    ) -> Result<Function, Error> {
+        let mut doms = am.get::<DominatorTree>(&BBFunction::from(func))?.clone();
+
+        log::info!("Numbering values of function {}", doms.cfg().name());
+        self.tree(&mut doms)?;
+
+        Ok(doms.into())
+    }
+
+    fn preserved(&self) -> PreservedAnalyses {
+        // GVN only rewrites instructions, never the control flow
+        PreservedAnalyses::none()
+            .preserve::<CFG>()
+            .preserve::<DominatorTree>()
+    }
+}
@@ -1,5 +1,7 @@
+mod gvn;
 mod lvn;
 mod tdce;
 
+pub use gvn::GVNPass;
 pub use lvn::LVNPass;
 pub use tdce::TDCEPass;
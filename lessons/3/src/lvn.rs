@@ -1,13 +1,15 @@
 use bril_rs::{Argument, ConstOps, Function, Instruction, Type, ValueOps};
-use itertools::Itertools;
 use std::collections::{HashMap, HashSet};
 use std::fmt::{Debug, Display};
 use utils::Foldable;
-use utils::{BasicBlock, FunctionPass, HashableLiteral, InstrExt, Pass};
+use utils::{
+    BasicBlock, CFG, DominatorTree, Error, FunctionPass, HashableLiteral, InstrExt, Pass,
+    PreservedAnalyses,
+};
 
 /// A value interned in the LVN table
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
-enum LVNValue {
+pub(crate) enum LVNValue {
     /// A Constant literal
     Literal(HashableLiteral),
     /// A Value operation
@@ -39,15 +41,17 @@ impl Display for LVNValue {
 
 /// The value table for the LVN pass
 #[derive(Default)]
-struct LVNTable {
+pub(crate) struct LVNTable {
     vid: usize,
     table: Vec<(String, LVNValue)>,
     vtable: HashMap<LVNValue, usize>,
     ntable: HashMap<String, usize>,
+    /// Values interned in each open scope, removed from `vtable` when the scope is popped
+    scopes: Vec<Vec<LVNValue>>,
 }
 
 impl LVNTable {
-    fn new(args: &Vec<Argument>) -> Self {
+    pub(crate) fn new(args: &Vec<Argument>) -> Self {
         let mut table = Self::default();
         for arg in args {
             let name = arg.name.clone();
@@ -58,7 +62,7 @@ impl LVNTable {
         table
     }
 
-    fn idx(&self, name: &str) -> Option<usize> {
+    pub(crate) fn idx(&self, name: &str) -> Option<usize> {
         self.ntable.get(name).copied()
     }
 
@@ -80,28 +84,28 @@ impl LVNTable {
         LVNValue::Unknown(vid)
     }
 
-    fn value(&self, idx: usize) -> &LVNValue {
+    pub(crate) fn value(&self, idx: usize) -> &LVNValue {
         &self.table[idx].1
     }
 
-    fn representative(&self, idx: usize) -> &String {
+    pub(crate) fn representative(&self, idx: usize) -> &String {
         &self.table[idx].0
     }
 
     /// Intern a value into the table
     /// Returns [None] if there is no value to be interned
     /// Returns [Some((bool, usize))] where the first value is true if the value already exists
-    pub fn intern(&mut self, instr: &Instruction) -> Option<(bool, usize)> {
+    pub fn intern(&mut self, instr: &Instruction) -> Result<Option<(bool, usize)>, Error> {
         // Try to intern the value
         let value = instr.fold(|k| {
             self.idx(k).and_then(|idx| match self.value(idx) {
                 LVNValue::Literal(l) => Some(l.clone().into()),
                 _ => None,
             })
-        });
+        })?;
 
         // If this instruction actually generates a value
-        value.map(|(name, value)| {
+        Ok(value.map(|(name, value)| {
             let value = match value {
                 Some(v) => LVNValue::Literal(v.into()),
                 None => {
@@ -144,11 +148,14 @@ impl LVNTable {
             } else {
                 let idx = self.table.len();
                 self.table.push((name.clone(), value.clone()));
+                if let Some(scope) = self.scopes.last_mut() {
+                    scope.push(value.clone());
+                }
                 self.vtable.insert(value, idx);
                 self.ntable.insert(name, idx);
                 (false, idx)
             }
-        })
+        }))
     }
 
     /// Transform an instruction using the table
@@ -177,7 +184,7 @@ impl LVNTable {
     }
 
     /// Build a reference instruction to a value
-    fn build_ref(&self, dest: &str) -> Option<Instruction> {
+    pub(crate) fn build_ref(&self, dest: &str) -> Option<Instruction> {
         self.idx(dest).and_then(|idx| match self.value(idx) {
             LVNValue::Literal(l) => Some(Instruction::Constant {
                 dest: dest.to_owned(),
@@ -204,6 +211,24 @@ impl LVNTable {
         self.table[idx].0 = name.to_owned();
         self.ntable.insert(name.to_owned(), idx);
     }
+
+    /// Map a name to an existing index without making it the representative
+    pub(crate) fn alias(&mut self, name: &str, idx: usize) {
+        self.ntable.insert(name.to_owned(), idx);
+    }
+
+    /// Open a scope, values interned from now on are forgotten when it is popped.
+    /// Names keep their indexes, so this is only sound when names are never reassigned.
+    pub(crate) fn push_scope(&mut self) {
+        self.scopes.push(Vec::new());
+    }
+
+    /// Forget the values interned since the matching [LVNTable::push_scope]
+    pub(crate) fn pop_scope(&mut self) {
+        for value in self.scopes.pop().unwrap_or_default() {
+            self.vtable.remove(&value);
+        }
+    }
 }
 
 impl Debug for LVNTable {
@@ -270,13 +295,20 @@ impl LVNPass {
 }
 
 impl Pass for LVNPass {
-    fn function(&mut self, func: Function) -> Function {
-        self.func(func.into()).into()
+    fn function(&mut self, func: Function) -> Result<Function, Error> {
+        Ok(self.func(func.into())?.into())
+    }
+
+    fn preserved(&self) -> PreservedAnalyses {
+        // LVN only rewrites instructions within blocks, never the control flow
+        PreservedAnalyses::none()
+            .preserve::<CFG>()
+            .preserve::<DominatorTree>()
     }
 }
 
 impl FunctionPass for LVNPass {
-    fn before(&mut self, func: utils::BBFunction) -> utils::BBFunction {
+    fn before(&mut self, func: utils::BBFunction) -> Result<utils::BBFunction, Error> {
         self.names
             .extend(func.args.iter().map(|arg| arg.name.clone()));
 
@@ -287,10 +319,10 @@ impl FunctionPass for LVNPass {
 
         self.args = func.args.clone();
 
-        func
+        Ok(func)
     }
 
-    fn basic_block(&mut self, bb: utils::BasicBlock) -> utils::BasicBlock {
+    fn basic_block(&mut self, bb: utils::BasicBlock) -> Result<utils::BasicBlock, Error> {
         self.table = LVNTable::new(&self.args);
         // Map of the instruction index to the last write of the variable
         let last_write_map: HashMap<_, _> = bb
@@ -309,7 +341,7 @@ impl FunctionPass for LVNPass {
             .map(|(i, instr)| {
                 log::debug!("Original: {}", instr);
                 let instr = self.table.transform(instr);
-                let instr = if let Some((new, idx)) = self.table.intern(&instr) {
+                let instr = if let Some((new, idx)) = self.table.intern(&instr)? {
                     log::debug!("\n{}", self.table);
                     let dest = instr.dest().unwrap();
                     if new {
@@ -345,10 +377,10 @@ impl FunctionPass for LVNPass {
                 };
 
                 log::debug!("Transformed: {}", instr);
-                instr
+                Ok(instr)
             })
-            .collect_vec();
+            .collect::<Result<Vec<_>, Error>>()?;
 
-        BasicBlock::new(bb.idx, bb.label, instrs)
+        Ok(BasicBlock::new(bb.idx, bb.label, instrs))
     }
 }
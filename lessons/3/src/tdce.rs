@@ -2,18 +2,18 @@ use std::collections::HashSet;
 
 use bril_rs::{Code, Function, Instruction};
 use itertools::Itertools;
-use utils::{BasicBlock, FunctionPass, Pass};
+use utils::{BasicBlock, Error, FunctionPass, Pass};
 
 pub struct TDCEPass;
 
 impl Pass for TDCEPass {
-    fn function(&mut self, func: Function) -> Function {
-        self.func(func.into()).into()
+    fn function(&mut self, func: Function) -> Result<Function, Error> {
+        Ok(self.func(func.into())?.into())
     }
 }
 
 impl FunctionPass for TDCEPass {
-    fn before(&mut self, func: utils::BBFunction) -> utils::BBFunction {
+    fn before(&mut self, func: utils::BBFunction) -> Result<utils::BBFunction, Error> {
         // Perform global DCE to remove totally unused instructions
         let mut changed = true;
         let mut func = Function::from(func);
@@ -61,10 +61,10 @@ impl FunctionPass for TDCEPass {
                 .collect();
         }
 
-        func.into()
+        Ok(func.into())
     }
 
-    fn basic_block(&mut self, bb: BasicBlock) -> BasicBlock {
+    fn basic_block(&mut self, bb: BasicBlock) -> Result<BasicBlock, Error> {
         let mut written_unread = HashSet::new();
 
         // Iterate in reverse to discard writes that occur
@@ -103,6 +103,6 @@ impl FunctionPass for TDCEPass {
 
         instrs.reverse();
 
-        BasicBlock::new(bb.idx, bb.label, instrs)
+        Ok(BasicBlock::new(bb.idx, bb.label, instrs))
     }
 }
@@ -1,16 +1,26 @@
+use argh::FromArgs;
 use bril_rs::{Instruction, ValueOps};
 use itertools::Itertools;
-use std::{
-    collections::{HashMap, HashSet},
-    fmt::{Debug, Display},
-};
+use serde::{Serialize, Serializer};
+use std::fmt::Display;
 use utils::{
-    AnalysisPass, BBFunction, BasicBlock, CFG, CallGraph, CanonicalizeLiterals, DataflowLabel,
-    DataflowPass, HashableLiteral, InstrExt, Pass, draw_dataflow, run_analysis,
-    setup_logger_from_env,
+    AnalysisPass, CFG, CallGraph, CanonicalizeLiterals, Confluence, DataflowLabel, HashableLiteral,
+    InstrExt, LatticePass, OutputFormat, Pass, PowerSet, draw_dataflow, export_dataflow,
+    run_analysis, setup_logger,
 };
 
-#[derive(Clone, PartialEq, Eq, Hash)]
+/// Compute the available expressions of the functions of a Bril program.
+#[derive(FromArgs)]
+struct Options {
+    /// log level
+    #[argh(option, short = 'l', default = "log::LevelFilter::Warn")]
+    log: log::LevelFilter,
+    /// output format, dot or json
+    #[argh(option, short = 'f', default = "OutputFormat::Dot")]
+    format: OutputFormat,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 enum Expr {
     /// Constant expression
     Const(HashableLiteral),
@@ -50,88 +60,30 @@ impl Display for Expr {
     }
 }
 
-#[derive(Clone, PartialEq, Eq)]
-enum Set {
-    /// Set of all items
-    Full,
-    /// Set of finite items
-    Finite(HashSet<Expr>),
-}
-
-impl Set {
-    fn empty() -> Self {
-        Set::Finite(HashSet::new())
-    }
-
-    fn full() -> Self {
-        Set::Full
-    }
-
-    fn intersect(&self, other: &Self) -> Self {
-        match (self, other) {
-            (Set::Full, _) => other.clone(),
-            (_, Set::Full) => self.clone(),
-            (Set::Finite(a), Set::Finite(b)) => Set::Finite(a.intersection(b).cloned().collect()),
-        }
-    }
-
-    fn insert(&mut self, expr: Expr) {
-        match self {
-            Set::Full => {}
-            Set::Finite(set) => {
-                set.insert(expr);
-            }
-        }
-    }
-}
-
-impl Debug for Set {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Set::Full => Display::fmt("T", f),
-            Set::Finite(set) => {
-                write!(f, "{{{}}}", set.iter().map(|e| format!("{}", e)).join(", "))
-            }
-        }
-    }
-}
+type Set = PowerSet<Expr>;
 
 #[derive(Default)]
 struct AvailableExpr;
 
-impl DataflowPass<Set> for AvailableExpr {
-    fn entry(&self, _: &BBFunction) -> Set {
-        // Entry block is empty
-        Set::empty()
-    }
-    fn init(&self, _: &BBFunction) -> Set {
-        Set::full()
-    }
-
-    fn meet(&self, in_vals: &[Set]) -> Set {
-        // Set intersection
-        in_vals
-            .iter()
-            .fold(Set::full(), |acc, val| acc.intersect(val))
+impl LatticePass<Set> for AvailableExpr {
+    fn confluence(&self) -> Confluence {
+        // Expressions must be available along every path
+        Confluence::Meet
     }
 
-    fn transfer(&self, block: &BasicBlock, in_val: &Set) -> Set {
-        let mut out_vals = in_val.clone();
+    fn transfer_instr(&self, instr: &Instruction, val: &Set) -> Option<Set> {
+        let mut out_vals = val.clone();
 
-        for instr in block.iter() {
-            if let Some(e) = Expr::new(instr) {
-                out_vals.insert(e);
-            }
+        if let Some(e) = Expr::new(instr) {
+            out_vals.insert(e);
+        }
 
-            if let Some(dest) = instr.dest() {
-                // Remove all expressions that contain the destination
-                if let Set::Finite(set) = &mut out_vals {
-                    set.retain(|expr| !expr.contains(&dest));
-                }
-            }
+        if let Some(dest) = instr.dest() {
+            // Remove all expressions that contain the destination
+            out_vals.retain(|expr| !expr.contains(&dest));
         }
 
-        out_vals
+        Some(out_vals)
     }
 }
 
@@ -172,28 +124,53 @@ impl DataflowLabel for GraphNode {
     }
 }
 
+/// The available expressions as sorted Bril text, or `"full"` for the set of every expression
+impl Serialize for GraphNode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.exprs {
+            Set::Finite(ref set) => {
+                serializer.collect_seq(set.iter().map(ToString::to_string).sorted_unstable())
+            }
+            Set::Full => serializer.serialize_str("full"),
+        }
+    }
+}
+
 impl From<Set> for GraphNode {
     fn from(exprs: Set) -> Self {
         Self { exprs }
     }
 }
 
-struct Drawer;
+struct Drawer {
+    format: OutputFormat,
+}
 
 impl AnalysisPass for Drawer {
     fn program(&mut self, prog: &bril_rs::Program) -> Result<(), String> {
-        let canonical = CanonicalizeLiterals.run(prog.clone());
+        let canonical = CanonicalizeLiterals
+            .run(prog.clone())
+            .map_err(|e| e.to_string())?;
         let call_graph = CallGraph::new(canonical.clone());
 
-        let dot = draw_dataflow::<AvailableExpr, Set, GraphNode>(call_graph, true, false);
+        let output = match self.format {
+            OutputFormat::Dot => {
+                draw_dataflow::<AvailableExpr, Set, GraphNode>(call_graph, true, false)
+            }
+            OutputFormat::Json => export_dataflow::<AvailableExpr, Set, GraphNode>(&call_graph),
+        }
+        .map_err(|e| e.to_string())?;
 
-        println!("{}", dot);
+        println!("{}", output);
 
         Ok(())
     }
 }
 
 fn main() {
-    setup_logger_from_env();
-    run_analysis(Drawer);
+    let opts: Options = argh::from_env();
+    setup_logger(opts.log);
+    run_analysis(Drawer {
+        format: opts.format,
+    });
 }
@@ -1,83 +1,31 @@
+use argh::FromArgs;
 use itertools::Itertools;
-use std::{collections::HashMap, fmt::Display, hash::Hash};
+use serde::Serialize;
 use utils::{
-    AnalysisPass, BBFunction, BasicBlock, CFG, CallGraph, CanonicalizeLiterals, DataflowLabel,
-    DataflowPass, Foldable, HashableLiteral, Pass, draw_dataflow, run_analysis,
-    setup_logger_from_env,
+    AnalysisPass, CFG, CallGraph, CanonicalizeLiterals, ConstProp, Constants, Dataflow,
+    DataflowLabel, Error, OutputFormat, Pass, draw, run_analysis, setup_logger, summarize, to_json,
 };
 
-#[derive(Clone, Debug, Eq, Hash, PartialEq)]
-enum Value {
-    Const(HashableLiteral),
-    Any,
-}
-
-impl Display for Value {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Value::Any => "T".fmt(f),
-            Value::Const(l) => l.fmt(f),
-        }
-    }
-}
-
-type Val = HashMap<String, Value>;
-
-#[derive(Default)]
-struct ConstProp;
-
-impl DataflowPass<Val> for ConstProp {
-    fn init(&self, _: &BBFunction) -> Val {
-        HashMap::default()
-    }
-
-    fn meet(&self, in_vals: &[Val]) -> Val {
-        let mut out_vals = HashMap::new();
-
-        // For every key, if it has multiple different bindings, set it to Any
-        // Otherwise, set it to the value
-        for (name, bind) in in_vals.iter().flat_map(|v| v.iter()) {
-            if let Some(v) = out_vals.get(name) {
-                if v != bind {
-                    out_vals.insert(name.clone(), Value::Any);
-                }
-            } else {
-                out_vals.insert(name.clone(), bind.clone());
-            }
-        }
-
-        out_vals
-    }
-
-    fn transfer(&self, block: &BasicBlock, in_val: &Val) -> Val {
-        let mut out_vals = in_val.clone();
-
-        for insn in block.iter() {
-            if let Some((dest, val)) = insn.fold(|arg| {
-                in_val.get(arg).and_then(|v| match v {
-                    Value::Const(c) => Some(c.clone().into()),
-                    Value::Any => None,
-                })
-            }) {
-                out_vals.insert(
-                    dest,
-                    match val {
-                        Some(v) => Value::Const(v.into()),
-                        None => Value::Any,
-                    },
-                );
-            }
-        }
-
-        out_vals
-    }
+/// Propagate constants through the functions of a Bril program.
+#[derive(FromArgs)]
+struct Options {
+    /// log level
+    #[argh(option, short = 'l', default = "log::LevelFilter::Warn")]
+    log: log::LevelFilter,
+    /// use the constants returned by callees at call sites
+    #[argh(switch, short = 'i')]
+    interprocedural: bool,
+    /// output format, dot or json
+    #[argh(option, short = 'f', default = "OutputFormat::Dot")]
+    format: OutputFormat,
 }
 
 /// Dominator set node used to display graphs
 /// For dominator sets
-#[derive(Clone)]
+#[derive(Clone, Serialize)]
+#[serde(transparent)]
 pub struct GraphNode {
-    vars: Val,
+    vars: Constants,
 }
 
 impl DataflowLabel for GraphNode {
@@ -100,28 +48,60 @@ impl DataflowLabel for GraphNode {
     }
 }
 
-impl From<Val> for GraphNode {
-    fn from(vars: Val) -> Self {
+impl From<Constants> for GraphNode {
+    fn from(vars: Constants) -> Self {
         Self { vars }
     }
 }
 
-struct Drawer;
+struct Drawer {
+    interprocedural: bool,
+    format: OutputFormat,
+}
 
 impl AnalysisPass for Drawer {
     fn program(&mut self, prog: &bril_rs::Program) -> Result<(), String> {
-        let canonical = CanonicalizeLiterals.run(prog.clone());
+        let canonical = CanonicalizeLiterals
+            .run(prog.clone())
+            .map_err(|e| e.to_string())?;
         let call_graph = CallGraph::new(canonical);
 
-        let dot = draw_dataflow::<ConstProp, Val, GraphNode>(call_graph, true, false);
-
-        println!("{}", dot);
+        let summaries = self
+            .interprocedural
+            .then(|| summarize(&call_graph))
+            .transpose()
+            .map_err(|e| e.to_string())?;
+        let results = call_graph
+            .prog()
+            .functions
+            .iter()
+            .map(|f| {
+                let mut pass = match &summaries {
+                    Some(summaries) => ConstProp::with_summaries(summaries),
+                    None => ConstProp::default(),
+                };
+                let cfg = CFG::try_from(f.clone())?;
+                Ok(Dataflow::<GraphNode>::from(pass.try_cfg(cfg)?))
+            })
+            .collect::<Result<Vec<_>, Error>>()
+            .map_err(|e| e.to_string())?;
+
+        let output = match self.format {
+            OutputFormat::Dot => draw((call_graph, results), true, false),
+            OutputFormat::Json => to_json(&results),
+        };
+
+        println!("{}", output);
 
         Ok(())
     }
 }
 
 fn main() {
-    setup_logger_from_env();
-    run_analysis(Drawer);
+    let opts: Options = argh::from_env();
+    setup_logger(opts.log);
+    run_analysis(Drawer {
+        interprocedural: opts.interprocedural,
+        format: opts.format,
+    });
 }
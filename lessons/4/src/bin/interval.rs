@@ -0,0 +1,476 @@
+use argh::FromArgs;
+use bril_rs::{Instruction, Literal, Type, ValueOps};
+use itertools::Itertools;
+use serde::{Serialize, Serializer};
+use std::fmt::Display;
+use utils::{
+    AnalysisPass, BBFunction, BasicBlock, CFG, CallGraph, DataflowLabel, EdgeKind, InstrExt,
+    Lattice, LatticePass, MapLattice, OutputFormat, draw_dataflow, export_dataflow, run_analysis,
+    setup_logger,
+};
+
+/// Compute the ranges of the integer variables of the functions of a Bril program.
+#[derive(FromArgs)]
+struct Options {
+    /// log level
+    #[argh(option, short = 'l', default = "log::LevelFilter::Warn")]
+    log: log::LevelFilter,
+    /// output format, dot or json
+    #[argh(option, short = 'f', default = "OutputFormat::Dot")]
+    format: OutputFormat,
+}
+
+/// Bound of an interval.
+/// The order of the variants matters, as it is used to derive the order of bounds.
+/// Bril integers are 64 bits, so the infinite bounds stand for the smallest and largest integers,
+/// and arithmetic on bounds overflows when it goes past them, as bril integers wrap around.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Bound {
+    NegInf,
+    Finite(i64),
+    PosInf,
+}
+
+impl Bound {
+    /// The integer a bound stands for
+    fn value(self) -> i64 {
+        match self {
+            Bound::NegInf => i64::MIN,
+            Bound::Finite(a) => a,
+            Bound::PosInf => i64::MAX,
+        }
+    }
+
+    fn from_value(a: i64) -> Bound {
+        match a {
+            i64::MIN => Bound::NegInf,
+            i64::MAX => Bound::PosInf,
+            a => Bound::Finite(a),
+        }
+    }
+
+    /// Sum of two bounds, [None] on overflow
+    fn add(self, other: Bound) -> Option<Bound> {
+        self.value()
+            .checked_add(other.value())
+            .map(Bound::from_value)
+    }
+
+    /// Negation of a bound, [None] on overflow
+    fn neg(self) -> Option<Bound> {
+        self.value().checked_neg().map(Bound::from_value)
+    }
+
+    /// Product of two bounds, [None] on overflow
+    fn mul(self, other: Bound) -> Option<Bound> {
+        self.value()
+            .checked_mul(other.value())
+            .map(Bound::from_value)
+    }
+
+    /// Quotient of two bounds, the divisor must not be zero.
+    /// Returns [None] on overflow.
+    fn div(self, other: Bound) -> Option<Bound> {
+        self.value()
+            .checked_div(other.value())
+            .map(Bound::from_value)
+    }
+}
+
+impl Display for Bound {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Bound::NegInf => write!(f, "-inf"),
+            Bound::Finite(a) => write!(f, "{}", a),
+            Bound::PosInf => write!(f, "inf"),
+        }
+    }
+}
+
+/// Interval of integers, including its bounds.
+/// An overflow of either bound gives the full range, as bril integers wrap around.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Interval {
+    Empty,
+    Range(Bound, Bound),
+}
+
+impl Interval {
+    fn new(lo: Bound, hi: Bound) -> Self {
+        if lo <= hi {
+            Interval::Range(lo, hi)
+        } else {
+            Interval::Empty
+        }
+    }
+
+    fn constant(c: i64) -> Self {
+        Interval::Range(Bound::from_value(c), Bound::from_value(c))
+    }
+
+    fn contains(&self, c: i64) -> bool {
+        match self {
+            Interval::Empty => false,
+            Interval::Range(lo, hi) => *lo <= Bound::from_value(c) && Bound::from_value(c) <= *hi,
+        }
+    }
+
+    /// Apply an operation to every pair of bounds, and take the smallest interval containing the results
+    fn corners(&self, other: &Self, op: fn(Bound, Bound) -> Option<Bound>) -> Self {
+        let (Interval::Range(a_lo, a_hi), Interval::Range(b_lo, b_hi)) = (*self, *other) else {
+            return Interval::Empty;
+        };
+
+        let corners = [(a_lo, b_lo), (a_lo, b_hi), (a_hi, b_lo), (a_hi, b_hi)]
+            .into_iter()
+            .map(|(a, b)| op(a, b))
+            .collect::<Option<Vec<_>>>();
+
+        match corners {
+            Some(corners) => Interval::new(
+                *corners.iter().min().unwrap(),
+                *corners.iter().max().unwrap(),
+            ),
+            None => Interval::top(),
+        }
+    }
+
+    fn add(&self, other: &Self) -> Self {
+        let (Interval::Range(a_lo, a_hi), Interval::Range(b_lo, b_hi)) = (*self, *other) else {
+            return Interval::Empty;
+        };
+
+        match (a_lo.add(b_lo), a_hi.add(b_hi)) {
+            (Some(lo), Some(hi)) => Interval::new(lo, hi),
+            _ => Interval::top(),
+        }
+    }
+
+    fn sub(&self, other: &Self) -> Self {
+        let (Interval::Range(a_lo, a_hi), Interval::Range(b_lo, b_hi)) = (*self, *other) else {
+            return Interval::Empty;
+        };
+
+        let lo = b_hi.neg().and_then(|b| a_lo.add(b));
+        let hi = b_lo.neg().and_then(|b| a_hi.add(b));
+
+        match (lo, hi) {
+            (Some(lo), Some(hi)) => Interval::new(lo, hi),
+            _ => Interval::top(),
+        }
+    }
+
+    fn mul(&self, other: &Self) -> Self {
+        self.corners(other, Bound::mul)
+    }
+
+    fn div(&self, other: &Self) -> Self {
+        if other.contains(0) {
+            // Division by zero is an error, so anything could happen
+            Interval::top()
+        } else {
+            self.corners(other, Bound::div)
+        }
+    }
+}
+
+impl Lattice for Interval {
+    fn bottom() -> Self {
+        Interval::Empty
+    }
+
+    fn top() -> Self {
+        Interval::Range(Bound::NegInf, Bound::PosInf)
+    }
+
+    fn join(&self, other: &Self) -> Self {
+        match (self, other) {
+            (Interval::Empty, x) | (x, Interval::Empty) => *x,
+            (Interval::Range(a_lo, a_hi), Interval::Range(b_lo, b_hi)) => {
+                Interval::Range(*a_lo.min(b_lo), *a_hi.max(b_hi))
+            }
+        }
+    }
+
+    fn meet(&self, other: &Self) -> Self {
+        match (self, other) {
+            (Interval::Empty, _) | (_, Interval::Empty) => Interval::Empty,
+            (Interval::Range(a_lo, a_hi), Interval::Range(b_lo, b_hi)) => {
+                Interval::new(*a_lo.max(b_lo), *a_hi.min(b_hi))
+            }
+        }
+    }
+
+    /// Bounds that are still growing are pushed to infinity
+    fn widen(&self, next: &Self) -> Self {
+        match (self, next) {
+            (Interval::Empty, x) | (x, Interval::Empty) => *x,
+            (Interval::Range(a_lo, a_hi), Interval::Range(b_lo, b_hi)) => Interval::Range(
+                if b_lo < a_lo { Bound::NegInf } else { *a_lo },
+                if b_hi > a_hi { Bound::PosInf } else { *a_hi },
+            ),
+        }
+    }
+
+    /// Only infinite bounds are refined, so narrowing always terminates
+    fn narrow(&self, next: &Self) -> Self {
+        match (self, next) {
+            (Interval::Empty, _) | (_, Interval::Empty) => Interval::Empty,
+            (Interval::Range(a_lo, a_hi), Interval::Range(b_lo, b_hi)) => Interval::new(
+                if *a_lo == Bound::NegInf { *b_lo } else { *a_lo },
+                if *a_hi == Bound::PosInf { *b_hi } else { *a_hi },
+            ),
+        }
+    }
+}
+
+impl Display for Interval {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Interval::Empty => write!(f, "⊥"),
+            Interval::Range(lo, hi) => write!(f, "[{}, {}]", lo, hi),
+        }
+    }
+}
+
+/// Maps integer variables to their range, variables that are not integers are not tracked
+type Val = MapLattice<String, Interval>;
+
+/// Find the comparison computing the branch condition of a block.
+/// Only comparisons in the same block whose arguments are not reassigned before the branch are found.
+fn branch_comparison(block: &BasicBlock) -> Option<(ValueOps, &str, &str)> {
+    let cond = block.branch_cond()?;
+    let mut assigned = vec![];
+
+    for instr in block.iter().rev() {
+        let Some(dest) = instr.dest() else {
+            continue;
+        };
+
+        if dest == cond {
+            return match instr {
+                Instruction::Value {
+                    op:
+                        op @ (ValueOps::Lt | ValueOps::Le | ValueOps::Gt | ValueOps::Ge | ValueOps::Eq),
+                    args,
+                    ..
+                } => match &args[..] {
+                    [a, b] if a != b && !assigned.contains(a) && !assigned.contains(b) => {
+                        Some((*op, a.as_str(), b.as_str()))
+                    }
+                    _ => None,
+                },
+                _ => None,
+            };
+        }
+
+        assigned.push(dest);
+    }
+
+    None
+}
+
+/// Refine the ranges of `lhs` and `rhs` knowing that `lhs < rhs`, or `lhs <= rhs` if not `strict`.
+/// Returns bottom if the comparison can not hold.
+fn refine_less(mut val: Val, lhs: &str, rhs: &str, strict: bool) -> Val {
+    let (Interval::Range(lhs_lo, _), Interval::Range(_, rhs_hi)) =
+        (*val.get(&lhs.to_owned()), *val.get(&rhs.to_owned()))
+    else {
+        return val;
+    };
+
+    let gap = if strict { 1 } else { 0 };
+
+    // An overflow means the bound can not be refined
+    let lhs_range = rhs_hi
+        .add(Bound::Finite(-gap))
+        .map_or(Interval::top(), |hi| Interval::new(Bound::NegInf, hi));
+    let rhs_range = lhs_lo
+        .add(Bound::Finite(gap))
+        .map_or(Interval::top(), |lo| Interval::new(lo, Bound::PosInf));
+
+    let lhs_range = val.get(&lhs.to_owned()).meet(&lhs_range);
+    let rhs_range = val.get(&rhs.to_owned()).meet(&rhs_range);
+
+    if lhs_range == Interval::Empty || rhs_range == Interval::Empty {
+        return Val::bottom();
+    }
+
+    val.insert(lhs.to_owned(), lhs_range);
+    val.insert(rhs.to_owned(), rhs_range);
+    val
+}
+
+/// Refine the ranges of `lhs` and `rhs` knowing that they are equal.
+/// Returns bottom if they can not be.
+fn refine_eq(mut val: Val, lhs: &str, rhs: &str) -> Val {
+    let range = val.get(&lhs.to_owned()).meet(val.get(&rhs.to_owned()));
+
+    if range == Interval::Empty {
+        return Val::bottom();
+    }
+
+    val.insert(lhs.to_owned(), range);
+    val.insert(rhs.to_owned(), range);
+    val
+}
+
+#[derive(Default)]
+struct IntervalAnalysis;
+
+impl LatticePass<Val> for IntervalAnalysis {
+    fn entry(&self, func: &BBFunction) -> Val {
+        // Arguments can be anything
+        let mut val = Val::bottom();
+        for arg in func.args.iter().filter(|arg| arg.arg_type == Type::Int) {
+            val.insert(arg.name.clone(), Interval::top());
+        }
+        val
+    }
+
+    fn transfer_instr(&self, instr: &Instruction, val: &Val) -> Option<Val> {
+        let mut out_val = val.clone();
+
+        match instr {
+            Instruction::Constant {
+                dest,
+                value: Literal::Int(c),
+                ..
+            } => out_val.insert(dest.clone(), Interval::constant(*c)),
+            Instruction::Value {
+                dest,
+                op,
+                args,
+                op_type: Type::Int,
+                ..
+            } => {
+                let arg = |i: usize| args.get(i).map_or(Interval::top(), |arg| *val.get(arg));
+
+                let range = match op {
+                    ValueOps::Id => arg(0),
+                    ValueOps::Add => arg(0).add(&arg(1)),
+                    ValueOps::Sub => arg(0).sub(&arg(1)),
+                    ValueOps::Mul => arg(0).mul(&arg(1)),
+                    ValueOps::Div => arg(0).div(&arg(1)),
+                    // Calls, loads, etc. can produce any integer
+                    _ => Interval::top(),
+                };
+
+                out_val.insert(dest.clone(), range);
+            }
+            // Other variables are not tracked
+            _ => {
+                if let Some(dest) = instr.dest() {
+                    out_val.insert(dest, Interval::bottom());
+                }
+            }
+        }
+
+        Some(out_val)
+    }
+
+    fn edge_transfer(&self, from: &BasicBlock, _: &BasicBlock, kind: EdgeKind, val: Val) -> Val {
+        let Some((op, a, b)) = branch_comparison(from) else {
+            return val;
+        };
+
+        match (op, kind == EdgeKind::True) {
+            (ValueOps::Lt, true) => refine_less(val, a, b, true),
+            (ValueOps::Lt, false) => refine_less(val, b, a, false),
+            (ValueOps::Le, true) => refine_less(val, a, b, false),
+            (ValueOps::Le, false) => refine_less(val, b, a, true),
+            (ValueOps::Gt, true) => refine_less(val, b, a, true),
+            (ValueOps::Gt, false) => refine_less(val, a, b, false),
+            (ValueOps::Ge, true) => refine_less(val, b, a, false),
+            (ValueOps::Ge, false) => refine_less(val, a, b, true),
+            (ValueOps::Eq, true) => refine_eq(val, a, b),
+            _ => val,
+        }
+    }
+
+    fn widen(&self, prev: &Val, next: Val) -> Val {
+        prev.widen(&next)
+    }
+
+    fn narrow(&self, prev: &Val, next: Val) -> Val {
+        prev.narrow(&next)
+    }
+
+    fn narrowing_iters(&self) -> usize {
+        3
+    }
+}
+
+/// Interval node used to display graphs
+#[derive(Clone)]
+pub struct GraphNode {
+    vars: Val,
+}
+
+impl GraphNode {
+    fn ranges(&self) -> String {
+        self.vars
+            .iter()
+            .map(|(var, range)| format!("{} = {}", var, range))
+            .sorted_unstable()
+            .join(", ")
+    }
+}
+
+impl DataflowLabel for GraphNode {
+    fn in_label(&self, _: &CFG) -> Option<String> {
+        Some(format!("In: {}\\l", self.ranges()))
+    }
+
+    fn out_label(&self, _: &CFG) -> Option<String> {
+        Some(format!("Out: {}\\l", self.ranges()))
+    }
+}
+
+/// The range of each variable as text, sorted by variable
+impl Serialize for GraphNode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(
+            self.vars
+                .iter()
+                .sorted_unstable_by(|(a, _), (b, _)| a.cmp(b))
+                .map(|(var, range)| (var, range.to_string())),
+        )
+    }
+}
+
+impl From<Val> for GraphNode {
+    fn from(vars: Val) -> Self {
+        Self { vars }
+    }
+}
+
+struct Drawer {
+    format: OutputFormat,
+}
+
+impl AnalysisPass for Drawer {
+    fn program(&mut self, prog: &bril_rs::Program) -> Result<(), String> {
+        let call_graph = CallGraph::new(prog.clone());
+
+        let output = match self.format {
+            OutputFormat::Dot => {
+                draw_dataflow::<IntervalAnalysis, Val, GraphNode>(call_graph, true, false)
+            }
+            OutputFormat::Json => export_dataflow::<IntervalAnalysis, Val, GraphNode>(&call_graph),
+        }
+        .map_err(|e| e.to_string())?;
+
+        println!("{}", output);
+
+        Ok(())
+    }
+}
+
+fn main() {
+    let opts: Options = argh::from_env();
+    setup_logger(opts.log);
+    run_analysis(Drawer {
+        format: opts.format,
+    });
+}
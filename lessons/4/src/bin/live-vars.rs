@@ -1,48 +1,27 @@
+use argh::FromArgs;
 use itertools::Itertools;
+use serde::{Serialize, Serializer};
 use std::collections::HashSet;
 use utils::{
-    AnalysisPass, BBFunction, BasicBlock, CFG, CallGraph, DataflowLabel, DataflowPass, InstrExt,
-    draw_dataflow, run_analysis, setup_logger_from_env,
+    AnalysisPass, BitSet, CFG, CallGraph, DataflowLabel, DataflowPass, Error, LiveVariables,
+    OutputFormat, Summaries, draw, run_analysis, setup_logger, summarize, to_json,
 };
 
-#[derive(Default)]
-struct LiveVars;
-
-impl DataflowPass<HashSet<String>> for LiveVars {
-    fn reversed(&self) -> bool {
-        true
-    }
-
-    fn init(&self, _: &BBFunction) -> HashSet<String> {
-        HashSet::default()
-    }
-
-    fn meet(&self, in_vals: &[HashSet<String>]) -> HashSet<String> {
-        // The meet in live vars is set union
-        in_vals.iter().flatten().cloned().collect()
-    }
-
-    fn transfer(&self, block: &BasicBlock, in_val: &HashSet<String>) -> HashSet<String> {
-        let mut out_vals = in_val.clone();
-
-        for insn in block.iter().rev() {
-            log::trace!("Processing instruction: {}", insn);
-
-            // Remove the destination from the set
-            if let Some(dest) = insn.dest() {
-                out_vals.remove(&dest);
-            }
-
-            // Add the arguments to the set
-            if let Some(args) = insn.args() {
-                for arg in args {
-                    out_vals.insert(arg);
-                }
-            }
-        }
-
-        out_vals
-    }
+/// Compute the live variables of the functions of a Bril program.
+#[derive(FromArgs)]
+struct Options {
+    /// log level
+    #[argh(option, short = 'l', default = "log::LevelFilter::Warn")]
+    log: log::LevelFilter,
+    /// compute the useful values instead, where calls only use the arguments their callee reads
+    #[argh(switch, short = 'i')]
+    interprocedural: bool,
+    /// output format, dot or json
+    #[argh(option, short = 'f', default = "OutputFormat::Dot")]
+    format: OutputFormat,
+    /// print the program with the variables live before and after each instruction instead
+    #[argh(switch, short = 'I')]
+    instrs: bool,
 }
 
 /// Dominator set node used to display graphs
@@ -74,27 +53,103 @@ impl DataflowLabel for GraphNode {
     }
 }
 
+/// The live variables in sorted order
+impl Serialize for GraphNode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.vars.iter().sorted_unstable())
+    }
+}
+
 impl From<HashSet<String>> for GraphNode {
     fn from(vars: HashSet<String>) -> Self {
         Self { vars }
     }
 }
 
-struct Drawer;
+struct Drawer {
+    interprocedural: bool,
+    format: OutputFormat,
+    instrs: bool,
+}
+
+/// Print a function with the live variables at every point between its instructions
+fn print_instrs(cfg: CFG, summaries: Option<&Summaries>) {
+    let mut analysis = match summaries {
+        Some(summaries) => LiveVariables::useful(cfg.func(), summaries),
+        None => LiveVariables::gen_kill(cfg.func()),
+    };
+    let dataflow = analysis.cfg(cfg);
+    let live = |set: &BitSet| {
+        let vars = analysis.items().decode(set);
+        format!(
+            "# live: {{{}}}",
+            vars.into_iter().sorted_unstable().join(", ")
+        )
+    };
+
+    println!("@{} {{", dataflow.cfg.name());
+    for (idx, block) in dataflow.cfg.iter().enumerate() {
+        println!(".{}:", block.label_or_default());
+
+        let vals = dataflow.instr_vals(&analysis, idx);
+        for (instr, val) in block.iter().zip(&vals) {
+            println!("  {}", live(val));
+            println!("  {}", instr);
+        }
+        println!("  {}", live(vals.last().unwrap()));
+    }
+    println!("}}");
+}
 
 impl AnalysisPass for Drawer {
     fn program(&mut self, prog: &bril_rs::Program) -> Result<(), String> {
         let call_graph = CallGraph::new(prog.clone());
+        let summaries = self
+            .interprocedural
+            .then(|| summarize(&call_graph))
+            .transpose()
+            .map_err(|e| e.to_string())?;
+
+        if self.instrs {
+            for f in &call_graph.prog().functions {
+                let cfg = CFG::try_from(f.clone()).map_err(|e| e.to_string())?;
+                print_instrs(cfg, summaries.as_ref());
+            }
+            return Ok(());
+        }
 
-        let dot = draw_dataflow::<LiveVars, HashSet<String>, GraphNode>(call_graph, true, false);
-
-        println!("{}", dot);
+        let results = call_graph
+            .prog()
+            .functions
+            .iter()
+            .map(|f| {
+                let cfg = CFG::try_from(f.clone())?;
+                let result = match &summaries {
+                    Some(summaries) => LiveVariables::run_useful(cfg, summaries),
+                    None => LiveVariables::run(cfg),
+                };
+                Ok(result.map(GraphNode::from))
+            })
+            .collect::<Result<Vec<_>, Error>>()
+            .map_err(|e| e.to_string())?;
+
+        let output = match self.format {
+            OutputFormat::Dot => draw((call_graph, results), true, false),
+            OutputFormat::Json => to_json(&results),
+        };
+
+        println!("{}", output);
 
         Ok(())
     }
 }
 
 fn main() {
-    setup_logger_from_env();
-    run_analysis(Drawer);
+    let opts: Options = argh::from_env();
+    setup_logger(opts.log);
+    run_analysis(Drawer {
+        interprocedural: opts.interprocedural,
+        format: opts.format,
+        instrs: opts.instrs,
+    });
 }
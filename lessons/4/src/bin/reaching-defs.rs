@@ -1,54 +1,49 @@
+use argh::FromArgs;
 use itertools::Itertools;
+use serde::{Serialize, Serializer};
 use std::collections::{HashMap, HashSet};
 use utils::{
-    AnalysisPass, BBFunction, BasicBlock, CFG, CallGraph, DataflowLabel, DataflowPass, InstrExt,
-    draw_dataflow, run_analysis, setup_logger_from_env,
+    AnalysisPass, BBFunction, CFG, CallGraph, DataflowLabel, DataflowPass, Error, GenKill,
+    InstrExt, OutputFormat, draw, run_analysis, setup_logger, to_json,
 };
 
-#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd)]
+/// Compute the reaching definitions of the functions of a Bril program.
+#[derive(FromArgs)]
+struct Options {
+    /// log level
+    #[argh(option, short = 'l', default = "log::LevelFilter::Warn")]
+    log: log::LevelFilter,
+    /// output format, dot or json
+    #[argh(option, short = 'f', default = "OutputFormat::Dot")]
+    format: OutputFormat,
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize)]
 struct Definition {
     name: String,
     block: usize,
 }
 
-#[derive(Default)]
-struct ReachingDefs;
-
-impl DataflowPass<HashSet<Definition>> for ReachingDefs {
-    fn init(&self, func: &BBFunction) -> HashSet<Definition> {
-        func.args
-            .iter()
-            .map(|arg| Definition {
-                block: 0,
-                name: arg.name.clone(),
-            })
-            .collect()
-    }
-
-    fn meet(&self, in_vals: &[HashSet<Definition>]) -> HashSet<Definition> {
-        // The meet in reaching definitions is set union
-        in_vals.iter().flatten().cloned().collect()
-    }
-
-    fn transfer(&self, block: &BasicBlock, in_val: &HashSet<Definition>) -> HashSet<Definition> {
-        // Set of defined names in this block
-        let defines: HashSet<_> = block.iter().filter_map(|insn| insn.dest()).collect();
-
-        // Kill all definitions in in_vals that write to this name
-        let mut out_vals: HashSet<_> = in_val
-            .iter()
-            .filter(|def| !defines.contains(&def.name))
-            .cloned()
-            .collect();
-
-        // Add definitions defined in the block
-        out_vals.extend(defines.into_iter().map(|name| Definition {
-            name,
-            block: block.idx,
-        }));
-
-        out_vals
-    }
+/// Build the reaching definitions analysis of a function.
+/// Each assignment kills all other definitions of its destination.
+fn reaching_defs(func: &BBFunction) -> GenKill<Definition> {
+    let args = func.args.iter().map(|arg| Definition {
+        block: 0,
+        name: arg.name.clone(),
+    });
+
+    GenKill::builder()
+        .entry(args)
+        .build(func, |block, insn, effects| {
+            if let Some(dest) = insn.dest() {
+                let name = dest.clone();
+                effects.kills_where(move |def: &Definition| def.name == name);
+                effects.generates(Definition {
+                    name: dest,
+                    block: block.idx,
+                });
+            }
+        })
 }
 
 /// Dominator set node used to display graphs
@@ -111,28 +106,55 @@ impl DataflowLabel for GraphNode {
     }
 }
 
+/// The definitions sorted by variable, then by block
+impl Serialize for GraphNode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.defs.iter().sorted_unstable())
+    }
+}
+
 impl From<HashSet<Definition>> for GraphNode {
     fn from(defs: HashSet<Definition>) -> Self {
         Self { defs }
     }
 }
 
-struct Drawer;
+struct Drawer {
+    format: OutputFormat,
+}
 
 impl AnalysisPass for Drawer {
     fn program(&mut self, prog: &bril_rs::Program) -> Result<(), String> {
         let call_graph = CallGraph::new(prog.clone());
 
-        let dot =
-            draw_dataflow::<ReachingDefs, HashSet<Definition>, GraphNode>(call_graph, true, false);
+        let results = call_graph
+            .prog()
+            .functions
+            .iter()
+            .map(|f| {
+                let cfg = CFG::try_from(f.clone())?;
+                let mut analysis = reaching_defs(cfg.func());
+                let result = analysis.cfg(cfg);
+                Ok(analysis.decode(result).map(GraphNode::from))
+            })
+            .collect::<Result<Vec<_>, Error>>()
+            .map_err(|e| e.to_string())?;
+
+        let output = match self.format {
+            OutputFormat::Dot => draw((call_graph, results), true, false),
+            OutputFormat::Json => to_json(&results),
+        };
 
-        println!("{}", dot);
+        println!("{}", output);
 
         Ok(())
     }
 }
 
 fn main() {
-    setup_logger_from_env();
-    run_analysis(Drawer);
+    let opts: Options = argh::from_env();
+    setup_logger(opts.log);
+    run_analysis(Drawer {
+        format: opts.format,
+    });
 }
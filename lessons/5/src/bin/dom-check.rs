@@ -51,11 +51,38 @@ impl DomChecker {
 
 impl AnalysisPass for DomChecker {
     fn function(&mut self, func: &bril_rs::Function) -> Result<(), String> {
-        let tree = DominatorTree::from(func.clone());
+        let tree = DominatorTree::try_from(func.clone()).map_err(|e| e.to_string())?;
+        let reachable = tree.cfg().reachable();
 
         for i in 0..tree.len() {
             let doms = tree.dominators(i);
             Self::check_paths(i, tree.cfg(), &doms)?;
+
+            // The constant time query must agree with the dominator chain
+            if let Some(j) = (0..tree.len()).find(|&j| tree.dominates(j, i) != doms.contains(&j)) {
+                return Err(format!(
+                    "Dominance query disagrees with the dominators of {} for {}",
+                    i, j
+                ));
+            }
+
+            // The frontier must match its definition, the blocks with a predecessor
+            // dominated by the block that are not strictly dominated by it.
+            // Dominance is only meaningful for the blocks that can run
+            if !reachable[i] {
+                continue;
+            }
+            let frontier = tree.dominance_frontier(i);
+            if let Some(j) = (0..tree.len()).filter(|&j| reachable[j]).find(|&j| {
+                let expected = !tree.strictly_dominates(i, j)
+                    && tree.cfg().preds(j).iter().any(|&p| tree.dominates(i, p));
+                frontier.contains(&j) != expected
+            }) {
+                return Err(format!(
+                    "Dominance frontier of {} disagrees with its definition for {}",
+                    i, j
+                ));
+            }
         }
 
         Ok(())
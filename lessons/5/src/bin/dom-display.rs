@@ -4,7 +4,7 @@ use graphviz_rust::{
     dot_structures::{Attribute, Id, Node, NodeId, Stmt},
 };
 use utils::{
-    AnalysisPass, BasicBlock, CallGraph, DominatorTree, GraphLike, draw, run_analysis,
+    AnalysisPass, BasicBlock, CallGraph, DominatorTree, Error, GraphLike, draw, run_analysis,
     setup_logger_from_env,
 };
 
@@ -78,9 +78,11 @@ impl GraphLike<&BasicBlock> for DomDisplay {
     }
 }
 
-impl From<Function> for DomDisplay {
-    fn from(func: Function) -> Self {
-        let tree = DominatorTree::from(func);
+impl TryFrom<Function> for DomDisplay {
+    type Error = Error;
+
+    fn try_from(func: Function) -> Result<Self, Error> {
+        let tree = DominatorTree::try_from(func)?;
 
         // Find the block in the function named "selected"
         let selected = tree
@@ -89,7 +91,7 @@ impl From<Function> for DomDisplay {
             .find(|bb| bb.label == Some("selected".to_owned()))
             .map(|bb| bb.idx);
 
-        Self { tree, selected }
+        Ok(Self { tree, selected })
     }
 }
 
@@ -100,7 +102,15 @@ impl AnalysisPass for DomDisplayPass {
     fn program(&mut self, prog: &bril_rs::Program) -> Result<(), String> {
         let call_graph = CallGraph::new(prog.clone());
 
-        let dot = draw::<DomDisplay>(call_graph, true, false);
+        let displays = prog
+            .functions
+            .iter()
+            .cloned()
+            .map(DomDisplay::try_from)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+
+        let dot = draw((call_graph, displays), true, false);
 
         println!("{}", dot);
 
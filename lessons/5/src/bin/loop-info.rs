@@ -0,0 +1,75 @@
+use itertools::Itertools;
+use utils::{AnalysisPass, CFG, DominatorTree, LoopForest, run_analysis, setup_logger_from_env};
+
+/// Print the natural loops of each function, from innermost to outermost,
+/// followed by any irreducible regions
+struct LoopInfo;
+
+impl AnalysisPass for LoopInfo {
+    fn function(&mut self, func: &bril_rs::Function) -> Result<(), String> {
+        let tree = DominatorTree::try_from(func.clone()).map_err(|e| e.to_string())?;
+        let loops = LoopForest::new(&tree);
+
+        let cfg = tree.cfg();
+        let label = |idx: usize| format!(".{}", cfg.get(idx).label_or_default());
+        let labels = |blocks: &mut dyn Iterator<Item = usize>| blocks.map(label).join(", ");
+
+        println!("@{}: {} loops", func.name, loops.len());
+        for l in loops.iter() {
+            println!("  loop {} (depth {}):", label(l.header()), l.depth());
+            println!("    body: {}", labels(&mut l.body()));
+            println!("    latches: {}", labels(&mut l.latches().iter().copied()));
+            if !l.exits().is_empty() {
+                println!(
+                    "    exits: {}",
+                    l.exits()
+                        .iter()
+                        .map(|&(from, to)| format!("{} -> {}", label(from), label(to)))
+                        .join(", ")
+                );
+            }
+            if let Some(preheader) = l.preheader() {
+                println!("    preheader: {}", label(preheader));
+            }
+            if let Some(parent) = l.parent() {
+                println!("    parent: {}", label(loops.get(parent).header()));
+            }
+        }
+
+        // Loops without a single header are not natural loops, so they are reported separately
+        for region in cfg.irreducible_regions() {
+            println!(
+                "  irreducible region entered at {}:",
+                labels(&mut region.entries().iter().copied())
+            );
+            println!("    body: {}", labels(&mut region.blocks()));
+        }
+
+        check_nesting(cfg, &loops)
+    }
+}
+
+/// Make sure each loop is contained in its parent and blocks are in their innermost loop
+fn check_nesting(cfg: &CFG, loops: &LoopForest) -> Result<(), String> {
+    for l in loops.iter() {
+        if let Some(parent) = l.parent().map(|p| loops.get(p))
+            && !l.body().all(|block| parent.contains(block))
+        {
+            return Err(format!("Loop {} is not nested in its parent", l.header()));
+        }
+    }
+
+    for block in 0..cfg.len() {
+        let containing = loops.iter().filter(|l| l.contains(block)).count();
+        if containing != loops.depth(block) {
+            return Err(format!("Block {} has the wrong loop depth", block));
+        }
+    }
+
+    Ok(())
+}
+
+fn main() {
+    setup_logger_from_env();
+    run_analysis(LoopInfo);
+}
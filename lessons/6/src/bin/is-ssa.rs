@@ -1,26 +1,14 @@
-use std::collections::HashSet;
-
-use bril_rs::{Code, Function};
-use utils::{AnalysisPass, InstrExt, run_analysis};
+use bril_rs::Function;
+use utils::{AnalysisPass, DominatorTree, VerifySSA, run_analysis};
 
 struct SSACheck;
 
 impl AnalysisPass for SSACheck {
     fn function(&mut self, func: &Function) -> Result<(), String> {
         // Check that the function is in SSA form
-        let mut vars = HashSet::new();
-        for instr in &func.instrs {
-            if let Code::Instruction(instr) = instr {
-                if let Some(dest) = instr.dest() {
-                    if vars.contains(&dest) {
-                        return Err(format!("Variable {} was assigned more than once", dest));
-                    }
-                    vars.insert(dest.clone());
-                }
-            }
-        }
-
-        Ok(())
+        DominatorTree::try_from(func.clone())
+            .and_then(|tree| VerifySSA.tree(&tree))
+            .map_err(|e| e.in_func(&func.name).to_string())
     }
 }
 
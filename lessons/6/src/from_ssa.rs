@@ -0,0 +1,435 @@
+use std::collections::{HashMap, HashSet};
+
+use bril_rs::{Argument, Code, EffectOps, Function, Instruction, Type, ValueOps};
+use linked_hash_map::LinkedHashMap;
+use utils::{BBFunction, CFG, Error, InstrExt, LiveVariables, Pass};
+
+/// Copies to make on each edge of a CFG, as maps from destination to source
+type EdgeCopies = LinkedHashMap<(usize, usize), LinkedHashMap<String, String>>;
+
+/// Pass to convert a program out of SSA form.
+///
+/// The `set`s into each block become a parallel copy on the edge they are executed on,
+/// which is placed in a new block if the edge is critical, and sequentialized with temporaries
+/// wherever the copies form a cycle. Copies between variables that do not interfere are then coalesced.
+pub struct FromSSA;
+
+impl Pass for FromSSA {
+    fn function(&mut self, func: Function) -> Result<Function, Error> {
+        // Because this is SSA, each variable is written only once so we can simply get its type!
+        let mut types: HashMap<_, _> = func
+            .instrs
+            .iter()
+            .filter_map(|instr| {
+                if let Code::Instruction(insn) = instr {
+                    insn.dest()
+                        .map(|dest| (dest.clone(), insn.get_type().unwrap()))
+                } else {
+                    None
+                }
+            })
+            .chain(
+                func.args
+                    .iter()
+                    .map(|Argument { name, arg_type }| (name.clone(), arg_type.clone())),
+            )
+            .collect();
+
+        let mut cfg = CFG::try_new(BBFunction::from(func))?;
+
+        let copies = FromSSA::collect_copies(&mut cfg, &types)?;
+        let inserted = FromSSA::place_copies(&mut cfg, copies, &mut types);
+
+        let names = FromSSA::coalesce(&cfg, &types);
+        log::debug!("Coalesced variables in @{}: {:?}", cfg.name(), names);
+        FromSSA::rename(&mut cfg, &names);
+
+        // Blocks added on critical edges are not needed if all of their copies were coalesced away
+        for (from, block, to) in inserted.into_iter().rev() {
+            if cfg.get(block).is_empty() {
+                cfg.retarget(from, block, to);
+                cfg.delete_block(block);
+            }
+        }
+
+        let mut func = Function::from(cfg);
+        for arg in &mut func.args {
+            rename(&names, &mut arg.name);
+        }
+
+        Ok(func)
+    }
+}
+
+impl FromSSA {
+    /// Remove the `get`s and `set`s of a function, and return the copies the `set`s make on each edge,
+    /// as a map from destination to source.
+    /// Each `set` is executed on the way to the block that reads its shadow variable with a `get`.
+    fn collect_copies(cfg: &mut CFG, types: &HashMap<String, Type>) -> Result<EdgeCopies, Error> {
+        let mut gets = HashMap::new();
+        for (bidx, block) in cfg.iter().enumerate() {
+            for instr in block.iter() {
+                if let Instruction::Value {
+                    op: ValueOps::Get,
+                    dest,
+                    pos,
+                    ..
+                } = instr
+                    && gets.insert(dest.clone(), bidx).is_some()
+                {
+                    return Err(Error::other(format!(
+                        "shadow variable {} is read by more than one get",
+                        dest
+                    ))
+                    .at(pos.clone()));
+                }
+            }
+        }
+
+        // A phi node only needs copies if its value is eventually read by something other than a `set`
+        let sets = cfg
+            .iter()
+            .flat_map(|block| block.iter())
+            .filter_map(|instr| match instr {
+                Instruction::Effect {
+                    args,
+                    op: EffectOps::Set,
+                    ..
+                } => Some(args),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let mut needed = cfg
+            .iter()
+            .flat_map(|block| block.iter())
+            .filter(|instr| {
+                !matches!(
+                    instr,
+                    Instruction::Effect {
+                        op: EffectOps::Set,
+                        ..
+                    }
+                )
+            })
+            .flat_map(|instr| instr.args().into_iter().flatten())
+            .collect::<HashSet<_>>();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for args in &sets {
+                if let [dest, src] = args.as_slice()
+                    && needed.contains(dest)
+                {
+                    changed |= needed.insert(src.clone());
+                }
+            }
+        }
+
+        let mut copies: LinkedHashMap<_, LinkedHashMap<_, _>> = LinkedHashMap::new();
+        for bidx in 0..cfg.len() {
+            let succs = cfg.succs(bidx);
+            let block = cfg.get_mut(bidx);
+            let label = block.label_or_default().to_owned();
+
+            let mut kept = vec![];
+            for instr in block.split_off(0) {
+                match &instr {
+                    Instruction::Effect {
+                        args,
+                        op: EffectOps::Set,
+                        pos,
+                        ..
+                    } => {
+                        let [dest, src] = args.as_slice() else {
+                            return Err(Error::other(format!(
+                                "set expects 2 arguments, got {}",
+                                args.len()
+                            ))
+                            .at(pos.clone()));
+                        };
+                        if !types.contains_key(src) {
+                            return Err(Error::other(format!("set of undefined variable {}", src))
+                                .at(pos.clone()));
+                        }
+
+                        // A shadow variable whose value is never read does not need a copy
+                        let Some(&target) = gets.get(dest).filter(|_| needed.contains(dest)) else {
+                            continue;
+                        };
+                        if !succs.contains(&target) {
+                            return Err(Error::other(format!(
+                                "set of {} in .{}, which does not lead to the get of it in .{}",
+                                dest,
+                                label,
+                                cfg.get(target).label_or_default()
+                            ))
+                            .at(pos.clone()));
+                        }
+
+                        copies
+                            .entry((bidx, target))
+                            .or_default()
+                            .insert(dest.clone(), src.clone());
+                    }
+                    Instruction::Value {
+                        op: ValueOps::Get, ..
+                    } => {}
+                    _ => kept.push(instr),
+                }
+            }
+
+            cfg.get_mut(bidx).extend(kept);
+        }
+
+        Ok(copies)
+    }
+
+    /// Place the copies of each edge where they only run when the edge is taken:
+    /// at the end of the source if it has one successor, at the start of the target if it has one predecessor,
+    /// and otherwise in a new block on the edge.
+    /// Edges are counted rather than blocks, since the copies of a branch with both labels
+    /// on the same block would otherwise run before the branch reads its condition.
+    /// Returns the blocks that were added, along with the edge each one is on.
+    fn place_copies(
+        cfg: &mut CFG,
+        copies: EdgeCopies,
+        types: &mut HashMap<String, Type>,
+    ) -> Vec<(usize, usize, usize)> {
+        let mut names = types.keys().cloned().collect::<HashSet<_>>();
+        let mut inserted = vec![];
+
+        for ((from, to), copies) in copies {
+            let instrs = sequentialize(copies, types, &mut names);
+            if instrs.is_empty() {
+                continue;
+            }
+
+            if cfg.succ_edges(from).len() == 1 {
+                cfg.get_mut(from).extend(instrs);
+            } else if cfg.pred_edges(to).len() == 1 {
+                let block = cfg.get_mut(to);
+                for (i, instr) in instrs.into_iter().enumerate() {
+                    block.insert(i, instr);
+                }
+            } else {
+                inserted.push((from, cfg.insert_block_on_edge(from, to, instrs), to));
+            }
+        }
+
+        inserted
+    }
+
+    /// Merge the variables of `id` copies that are never live at the same time, so that the copies can be removed.
+    /// Returns the name each merged variable is replaced with.
+    fn coalesce(cfg: &CFG, types: &HashMap<String, Type>) -> HashMap<String, String> {
+        let args = cfg
+            .func()
+            .args
+            .iter()
+            .map(|arg| arg.name.clone())
+            .collect::<HashSet<_>>();
+
+        // A variable interferes with every variable live after it is written,
+        // except the variable it is copied from
+        let live = LiveVariables::run(cfg.clone());
+        let mut interference: HashMap<String, HashSet<String>> = HashMap::new();
+        let mut add_edge = |a: &String, b: &String| {
+            if a != b {
+                interference.entry(a.clone()).or_default().insert(b.clone());
+                interference.entry(b.clone()).or_default().insert(a.clone());
+            }
+        };
+
+        // The arguments are all written on entry. The analysis runs backwards,
+        // so the out value of a block holds at its start.
+        let entry_live = live.out_vals.first().cloned().unwrap_or_default();
+        for arg in &args {
+            for other in args.iter().chain(&entry_live) {
+                add_edge(arg, other);
+            }
+        }
+
+        let mut copies = vec![];
+        for (bidx, block) in cfg.iter().enumerate() {
+            let mut block_copies = vec![];
+            let mut live_now = live.in_vals[bidx].clone();
+            for instr in block.iter().rev() {
+                let copied = copy_source(instr);
+                if let Some(dest) = instr.dest() {
+                    for other in &live_now {
+                        if Some(other) != copied {
+                            add_edge(&dest, other);
+                        }
+                    }
+                    live_now.remove(&dest);
+
+                    if let Some(src) = copied {
+                        block_copies.push((dest, src.clone()));
+                    }
+                }
+
+                live_now.extend(instr.args().into_iter().flatten());
+            }
+
+            // Copies are visited in program order so that the result does not depend on hashing
+            copies.extend(block_copies.into_iter().rev());
+        }
+
+        let mut classes: HashMap<String, Vec<String>> = HashMap::new();
+        let mut names: HashMap<String, String> = HashMap::new();
+        for (dest, src) in copies {
+            let a = names.get(&dest).cloned().unwrap_or(dest);
+            let b = names.get(&src).cloned().unwrap_or(src);
+            if a == b || types.get(&a) != types.get(&b) {
+                continue;
+            }
+
+            let members = |rep: &String| classes.get(rep).cloned().unwrap_or(vec![rep.clone()]);
+            let (a_members, b_members) = (members(&a), members(&b));
+            let interferes = a_members.iter().any(|m| {
+                interference
+                    .get(m)
+                    .is_some_and(|others| b_members.iter().any(|n| others.contains(n)))
+            });
+            if interferes {
+                continue;
+            }
+
+            // Arguments keep their names, since they are part of the signature
+            let (rep, merged) = if b_members.iter().any(|m| args.contains(m)) {
+                (b, a_members)
+            } else {
+                (a, b_members)
+            };
+            for member in &merged {
+                names.insert(member.clone(), rep.clone());
+            }
+            classes.remove(&merged[0]);
+            let class = classes.entry(rep.clone()).or_insert(vec![rep]);
+            class.extend(merged);
+        }
+
+        names
+    }
+
+    /// Give every variable its coalesced name, removing the copies that became redundant
+    /// along with the `undef`s that are no longer read
+    fn rename(cfg: &mut CFG, names: &HashMap<String, String>) {
+        for block in cfg.iter_mut() {
+            let instrs = block.split_off(0);
+            block.extend(instrs.into_iter().filter_map(|mut instr| {
+                rename_instr(names, &mut instr);
+                (!is_self_copy(&instr)).then_some(instr)
+            }));
+        }
+
+        let used = cfg
+            .iter()
+            .flat_map(|block| block.iter())
+            .flat_map(|instr| instr.args().into_iter().flatten())
+            .collect::<HashSet<_>>();
+        for block in cfg.iter_mut() {
+            let instrs = block.split_off(0);
+            block.extend(instrs.into_iter().filter(|instr| match instr {
+                Instruction::Value {
+                    op: ValueOps::Undef,
+                    dest,
+                    ..
+                } => used.contains(dest),
+                _ => true,
+            }));
+        }
+    }
+}
+
+/// Turn a parallel copy into a sequence of `id` instructions with the same effect.
+/// A copy can run once no other copy still needs to read its destination,
+/// and when only cycles are left one of their destinations is saved in a temporary.
+fn sequentialize(
+    copies: LinkedHashMap<String, String>,
+    types: &mut HashMap<String, Type>,
+    names: &mut HashSet<String>,
+) -> Vec<Instruction> {
+    let mut pending = copies
+        .into_iter()
+        .filter(|(dest, src)| dest != src)
+        .collect::<Vec<_>>();
+
+    let mut instrs = vec![];
+    while !pending.is_empty() {
+        if let Some(i) = pending
+            .iter()
+            .position(|(dest, _)| pending.iter().all(|(_, src)| src != dest))
+        {
+            let (dest, src) = pending.remove(i);
+            instrs.push(copy(dest, src, types));
+            continue;
+        }
+
+        let dest = pending[0].0.clone();
+        let tmp = (0..)
+            .map(|i| format!("{}.tmp{}", dest, i))
+            .find(|name| !names.contains(name))
+            .unwrap();
+        names.insert(tmp.clone());
+        types.insert(tmp.clone(), types[&dest].clone());
+        log::debug!("Breaking copy cycle through {} with {}", dest, tmp);
+
+        instrs.push(copy(tmp.clone(), dest.clone(), types));
+        for (_, src) in &mut pending {
+            if *src == dest {
+                *src = tmp.clone();
+            }
+        }
+    }
+
+    instrs
+}
+
+fn copy(dest: String, src: String, types: &HashMap<String, Type>) -> Instruction {
+    Instruction::Value {
+        op_type: types[&dest].clone(),
+        dest,
+        op: ValueOps::Id,
+        args: vec![src],
+        funcs: vec![],
+        labels: vec![],
+        pos: None,
+    }
+}
+
+/// Variable an instruction copies, if it is an `id`
+fn copy_source(instr: &Instruction) -> Option<&String> {
+    match instr {
+        Instruction::Value {
+            op: ValueOps::Id,
+            args,
+            ..
+        } => args.first(),
+        _ => None,
+    }
+}
+
+fn is_self_copy(instr: &Instruction) -> bool {
+    match instr {
+        Instruction::Value { dest, .. } => copy_source(instr) == Some(dest),
+        _ => false,
+    }
+}
+
+fn rename(names: &HashMap<String, String>, name: &mut String) {
+    if let Some(new) = names.get(name) {
+        *name = new.clone();
+    }
+}
+
+fn rename_instr(names: &HashMap<String, String>, instr: &mut Instruction) {
+    match instr {
+        Instruction::Constant { dest, .. } => rename(names, dest),
+        Instruction::Value { args, dest, .. } => {
+            args.iter_mut().for_each(|arg| rename(names, arg));
+            rename(names, dest);
+        }
+        Instruction::Effect { args, .. } => args.iter_mut().for_each(|arg| rename(names, arg)),
+    }
+}
@@ -0,0 +1,3 @@
+mod from_ssa;
+
+pub use from_ssa::FromSSA;
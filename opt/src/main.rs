@@ -0,0 +1,50 @@
+use argh::FromArgs;
+use lesson_3::{GVNPass, LVNPass, TDCEPass};
+use lesson_6::FromSSA;
+use utils::{PassRegistry, run_passes, setup_logger};
+
+/// Run a pipeline of optimization passes over a Bril program.
+#[derive(FromArgs)]
+struct Options {
+    /// log level
+    #[argh(option, short = 'l', default = "log::LevelFilter::Warn")]
+    log: log::LevelFilter,
+    /// comma separated list of passes to run in order, e.g. `canonicalize,lvn,tdce`
+    #[argh(option, short = 'p', default = "String::new()")]
+    passes: String,
+    /// list the available passes and exit
+    #[argh(switch)]
+    list: bool,
+}
+
+/// Registry of every pass available to the driver
+fn registry() -> PassRegistry {
+    let mut registry = PassRegistry::new();
+    registry
+        .register("lvn", || Box::new(LVNPass::default()))
+        .register("gvn", || Box::new(GVNPass::default()))
+        .register("tdce", || Box::new(TDCEPass))
+        .register("from-ssa", || Box::new(FromSSA));
+    registry
+}
+
+fn main() {
+    let args: Options = argh::from_env();
+    setup_logger(args.log);
+
+    let registry = registry();
+
+    if args.list {
+        for name in registry.names() {
+            println!("{}", name);
+        }
+        return;
+    }
+
+    let mut passes = registry.pipeline(&args.passes).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+
+    run_passes(&mut passes);
+}
@@ -169,10 +169,20 @@ class DataFlowPass(FunctionPass):
 
         preds, succs = generate_cfg(self.blocks)
 
+        # Blocks that can never run are left at their initial values,
+        # and do not contribute to the values of other blocks
+        reachable = set()
+        stack = [0] if self.blocks else []
+        while stack:
+            b = stack.pop()
+            if b not in reachable:
+                reachable.add(b)
+                stack += succs[b]
+
         if self.reverse:
             preds, succs = succs, preds
 
-        worklist = list(range(len(self.blocks)))
+        worklist = [b for b in range(len(self.blocks)) if b in reachable]
 
         def is_entry(bidx):
             if not self.reverse:
@@ -183,7 +193,7 @@ class DataFlowPass(FunctionPass):
         while len(worklist) > 0:
             b = worklist.pop(0)
 
-            inputs = [self.out_values[p] for p in preds[b]]
+            inputs = [self.out_values[p] for p in preds[b] if p in reachable]
             if is_entry(b):
                 inputs.append(self.args())
             self.in_values[b] = self.meet(inputs)
@@ -191,7 +201,7 @@ class DataFlowPass(FunctionPass):
             new_out = self.transfer(b)
             if new_out != self.out_values[b]:
                 self.out_values[b] = new_out
-                worklist += succs[b]
+                worklist += [s for s in succs[b] if s in reachable]
 
         # Now that we have the final values, we can apply them to the function
         super().run()
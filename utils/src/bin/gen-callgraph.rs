@@ -4,8 +4,9 @@ use argh::FromArgs;
 use bril_rs::Function;
 use std::default::Default;
 use utils::{
-    draw, run_analysis, setup_logger, AnalysisPass, CallGraph, Dataflow, DataflowPass,
-    DominatorPass, DominatorSetNode, DominatorTree, CFG,
+    draw, run_analysis, setup_logger, to_json, AnalysisPass, CallGraph, ControlDependenceGraph,
+    Dataflow, DataflowPass, DominatorPass, DominatorSetNode, DominatorTree, Error, OutputFormat,
+    PostDominatorTree, CFG,
 };
 
 pub enum SubgraphTypes {
@@ -13,6 +14,8 @@ pub enum SubgraphTypes {
     CFG,
     DominatorTree,
     DominatorSets,
+    PostDominatorTree,
+    ControlDependence,
 }
 
 impl FromStr for SubgraphTypes {
@@ -24,6 +27,8 @@ impl FromStr for SubgraphTypes {
             "cfg" => Ok(Self::CFG),
             "dominatortree" | "domtree" => Ok(Self::DominatorTree),
             "dominatorsets" | "domsets" => Ok(Self::DominatorSets),
+            "postdominatortree" | "postdomtree" | "pdomtree" => Ok(Self::PostDominatorTree),
+            "controldependence" | "cdg" => Ok(Self::ControlDependence),
             _ => Err(format!("Unknown subgraph type: {}", s)),
         }
     }
@@ -38,41 +43,91 @@ struct Options {
     /// subgraph type to draw
     #[argh(option, short = 's', default = "SubgraphTypes::None")]
     subgraph: SubgraphTypes,
+    /// output format, dot or json
+    #[argh(option, short = 'f', default = "OutputFormat::Dot")]
+    format: OutputFormat,
 }
 
 struct CallDrawer {
     sg_ty: SubgraphTypes,
+    format: OutputFormat,
 }
 
 impl CallDrawer {
-    pub fn new(sg_ty: SubgraphTypes) -> Self {
-        Self { sg_ty }
+    pub fn new(sg_ty: SubgraphTypes, format: OutputFormat) -> Self {
+        Self { sg_ty, format }
+    }
+
+    /// Export the call graph, or the subgraph of each function, as JSON
+    fn export(&self, prog: &bril_rs::Program, call_graph: &CallGraph) -> Result<String, Error> {
+        Ok(match self.sg_ty {
+            SubgraphTypes::None => to_json(call_graph),
+            SubgraphTypes::CFG => to_json(&subgraphs::<CFG>(prog)?),
+            SubgraphTypes::DominatorTree => to_json(&subgraphs::<DominatorTree>(prog)?),
+            SubgraphTypes::DominatorSets => to_json(&dominator_sets(prog)?),
+            SubgraphTypes::PostDominatorTree => to_json(&subgraphs::<PostDominatorTree>(prog)?),
+            SubgraphTypes::ControlDependence => {
+                to_json(&subgraphs::<ControlDependenceGraph>(prog)?)
+            }
+        })
+    }
+
+    /// Draw the call graph, with the subgraph of each function inside its node
+    fn draw(&self, prog: &bril_rs::Program, call_graph: CallGraph) -> Result<String, Error> {
+        let strict = false;
+
+        Ok(match self.sg_ty {
+            SubgraphTypes::None => draw::<Function>(call_graph, true, strict),
+            SubgraphTypes::CFG => draw((call_graph, subgraphs::<CFG>(prog)?), true, strict),
+            SubgraphTypes::DominatorTree => draw(
+                (call_graph, subgraphs::<DominatorTree>(prog)?),
+                true,
+                strict,
+            ),
+            SubgraphTypes::DominatorSets => draw((call_graph, dominator_sets(prog)?), true, strict),
+            SubgraphTypes::PostDominatorTree => draw(
+                (call_graph, subgraphs::<PostDominatorTree>(prog)?),
+                true,
+                strict,
+            ),
+            SubgraphTypes::ControlDependence => draw(
+                (call_graph, subgraphs::<ControlDependenceGraph>(prog)?),
+                true,
+                strict,
+            ),
+        })
     }
 }
 
+/// Build the subgraph of each function of the program
+fn subgraphs<SG: TryFrom<Function, Error = Error>>(
+    prog: &bril_rs::Program,
+) -> Result<Vec<SG>, Error> {
+    prog.functions.iter().cloned().map(SG::try_from).collect()
+}
+
+/// Compute the dominators of each block of each function of the program
+fn dominator_sets(prog: &bril_rs::Program) -> Result<Vec<Dataflow<DominatorSetNode>>, Error> {
+    prog.functions
+        .iter()
+        .map(|f| {
+            let cfg = CFG::try_from(f.clone())?;
+            Ok(<Dataflow<DominatorSetNode>>::from(DominatorPass.cfg(cfg)))
+        })
+        .collect()
+}
+
 impl AnalysisPass for CallDrawer {
     fn program(&mut self, prog: &bril_rs::Program) -> Result<(), String> {
         let call_graph = CallGraph::new(prog.clone());
 
-        let strict = false;
-
-        let dot = match self.sg_ty {
-            SubgraphTypes::None => draw::<Function>(call_graph, true, strict),
-            SubgraphTypes::CFG => draw::<CFG>(call_graph, true, strict),
-            SubgraphTypes::DominatorTree => draw::<DominatorTree>(call_graph, true, strict),
-            SubgraphTypes::DominatorSets => {
-                // Collect all the dominators
-                let dominators = prog
-                    .functions
-                    .iter()
-                    .map(|f| DominatorPass.cfg(CFG::from(f.clone())))
-                    .map(<Dataflow<DominatorSetNode>>::from)
-                    .collect();
-                draw((call_graph, dominators), true, strict)
-            }
-        };
+        let output = match self.format {
+            OutputFormat::Dot => self.draw(prog, call_graph),
+            OutputFormat::Json => self.export(prog, &call_graph),
+        }
+        .map_err(|e| e.to_string())?;
 
-        println!("{}", dot);
+        println!("{}", output);
 
         Ok(())
     }
@@ -81,5 +136,5 @@ impl AnalysisPass for CallDrawer {
 fn main() {
     let args: Options = argh::from_env();
     setup_logger(args.log);
-    run_analysis(CallDrawer::new(args.subgraph));
+    run_analysis(CallDrawer::new(args.subgraph, args.format));
 }
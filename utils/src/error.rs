@@ -0,0 +1,167 @@
+use bril_rs::{Literal, Position, Type};
+use std::fmt::{self, Display};
+
+/// The kinds of errors that can occur while analyzing or transforming a program
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorKind {
+    /// The input could not be parsed into a program
+    Parse(String),
+    /// A jump or branch refers to a label that does not exist
+    UnknownLabel(String),
+    /// An operation was given the wrong number or type of arguments
+    InvalidArgs { op: String, args: Vec<Literal> },
+    /// A literal cannot be cast to the given type
+    InvalidCast { value: Literal, ty: Type },
+    /// Control flow that is not made of natural loops, given as the labels of the blocks in each irreducible region
+    Irreducible(Vec<Vec<String>>),
+    /// Any other error
+    Other(String),
+}
+
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorKind::Parse(e) => write!(f, "failed to parse program: {}", e),
+            ErrorKind::UnknownLabel(label) => write!(f, "unknown label .{}", label),
+            ErrorKind::InvalidArgs { op, args } => {
+                write!(f, "invalid arguments for {}: ", op)?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", arg, arg.get_type())?;
+                }
+                Ok(())
+            }
+            ErrorKind::InvalidCast { value, ty } => {
+                write!(f, "cannot cast {}: {} to {}", value, value.get_type(), ty)
+            }
+            ErrorKind::Irreducible(regions) => {
+                write!(f, "irreducible control flow in ")?;
+                for (i, region) in regions.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{{")?;
+                    for (j, label) in region.iter().enumerate() {
+                        if j > 0 {
+                            write!(f, ", ")?;
+                        }
+                        write!(f, ".{}", label)?;
+                    }
+                    write!(f, "}}")?;
+                }
+                Ok(())
+            }
+            ErrorKind::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// Where in a program an error occurred
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Location {
+    /// Name of the function the error occurred in
+    pub func: Option<String>,
+    /// Label of the block the error occurred in
+    pub block: Option<String>,
+    /// Source position of the offending instruction
+    pub pos: Option<Position>,
+}
+
+/// An error along with where in the program it occurred
+#[derive(Debug, Clone, PartialEq)]
+pub struct Error {
+    pub kind: ErrorKind,
+    /// Boxed to keep results small
+    pub location: Box<Location>,
+}
+
+impl Error {
+    pub fn new(kind: ErrorKind) -> Self {
+        Self {
+            kind,
+            location: Box::default(),
+        }
+    }
+
+    pub fn other(msg: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Other(msg.into()))
+    }
+
+    /// Set the function of the error, if it isn't already known
+    pub fn in_func(mut self, name: &str) -> Self {
+        self.location.func.get_or_insert_with(|| name.to_owned());
+        self
+    }
+
+    /// Set the block of the error, if it isn't already known
+    pub fn in_block(mut self, label: &str) -> Self {
+        self.location.block.get_or_insert_with(|| label.to_owned());
+        self
+    }
+
+    /// Set the source position of the error, if it isn't already known
+    pub fn at(mut self, pos: Option<Position>) -> Self {
+        if self.location.pos.is_none() {
+            self.location.pos = pos;
+        }
+        self
+    }
+
+    /// Print the error to stderr, along with the offending source line if it is available
+    pub fn report(&self) {
+        eprintln!("error: {}", self);
+
+        let Some(Position {
+            pos,
+            src: Some(src),
+            ..
+        }) = &self.location.pos
+        else {
+            return;
+        };
+
+        let line = std::fs::read_to_string(src).ok().and_then(|f| {
+            f.lines()
+                .nth((pos.row as usize).saturating_sub(1))
+                .map(str::to_owned)
+        });
+
+        if let Some(line) = line {
+            eprintln!("{}", line);
+            eprintln!("{:>width$}", "^", width = pos.col as usize);
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.kind)?;
+
+        let Location { func, block, pos } = &*self.location;
+
+        if let Some(func) = func {
+            write!(f, " in @{}", func)?;
+        }
+        if let Some(block) = block {
+            write!(f, " in block .{}", block)?;
+        }
+        if let Some(Position { pos, src, .. }) = pos {
+            match src {
+                Some(src) => write!(f, " at {}:{}:{}", src, pos.row, pos.col)?,
+                None => write!(f, " at {}:{}", pos.row, pos.col)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Self {
+        Self::new(kind)
+    }
+}
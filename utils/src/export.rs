@@ -0,0 +1,29 @@
+use serde::Serialize;
+use std::str::FromStr;
+
+/// Format the analysis tools print their results in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// Graphviz text, see [crate::draw]
+    #[default]
+    Dot,
+    /// JSON, for scripts that need the structure of the results
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dot" => Ok(Self::Dot),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("Unknown output format: {}", s)),
+        }
+    }
+}
+
+/// Print a value as pretty JSON
+pub fn to_json(value: &impl Serialize) -> String {
+    serde_json::to_string_pretty(value).expect("Analysis results can always be serialized")
+}
@@ -1,6 +1,8 @@
 use bril_rs::{ConstOps, EffectOps, Instruction, Literal, Type, ValueOps};
 use std::fmt::Write;
 
+use crate::{Error, ErrorKind};
+
 pub trait InstrExt {
     fn args(&self) -> Option<Vec<String>>;
     fn dest(&self) -> Option<String>;
@@ -189,20 +191,25 @@ impl InstrExt for Instruction {
     }
 }
 
-pub trait LiteralExt {
+pub trait LiteralExt: Sized {
     /// Implicitly cast the literal to the given type
-    fn implicit_cast(&self, ty: &Type) -> Self;
+    fn implicit_cast(&self, ty: &Type) -> Result<Self, Error>;
 }
 
 impl LiteralExt for Literal {
-    fn implicit_cast(&self, ty: &Type) -> Self {
-        match (self, ty) {
+    fn implicit_cast(&self, ty: &Type) -> Result<Self, Error> {
+        Ok(match (self, ty) {
             (Literal::Int(i), Type::Int) => Literal::Int(*i),
             (Literal::Bool(b), Type::Bool) => Literal::Bool(*b),
             (Literal::Int(i), Type::Float) => Literal::Float(*i as f64),
             (Literal::Float(f), Type::Float) => Literal::Float(*f),
             (Literal::Char(c), Type::Char) => Literal::Char(*c),
-            _ => unreachable!(),
-        }
+            _ => {
+                return Err(Error::new(ErrorKind::InvalidCast {
+                    value: self.clone(),
+                    ty: ty.clone(),
+                }));
+            }
+        })
     }
 }
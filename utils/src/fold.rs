@@ -1,12 +1,26 @@
+use crate::{Error, ErrorKind};
 use bril_rs::{Instruction, Literal, ValueOps};
 
 pub trait Foldable<K, V> {
-    fn fold(&self, f: impl Fn(&K) -> Option<V>) -> Option<(K, Option<V>)>;
+    /// Fold the value written by this, using `f` to look up the values of arguments.
+    /// Returns [None] if nothing is written, and an error if the arguments are ill-typed.
+    fn fold(&self, f: impl Fn(&K) -> Option<V>) -> Result<Option<(K, Option<V>)>, Error>;
 }
 
 impl Foldable<String, Literal> for Instruction {
-    fn fold(&self, f: impl Fn(&String) -> Option<Literal>) -> Option<(String, Option<Literal>)> {
-        match self {
+    fn fold(
+        &self,
+        f: impl Fn(&String) -> Option<Literal>,
+    ) -> Result<Option<(String, Option<Literal>)>, Error> {
+        let invalid = |op: &ValueOps, args: &[Literal]| {
+            Error::new(ErrorKind::InvalidArgs {
+                op: op.to_string(),
+                args: args.to_vec(),
+            })
+            .at(self.get_pos())
+        };
+
+        Ok(match self {
             Instruction::Constant { dest, value, .. } => Some((dest.clone(), Some(value.clone()))),
             Instruction::Value { dest, op, args, .. } => Some((
                 dest.clone(),
@@ -14,7 +28,7 @@ impl Foldable<String, Literal> for Instruction {
                     match op {
                         ValueOps::Id => {
                             let [ref a] = args[..] else {
-                                panic!("Id expects one argument, got {:?}", args);
+                                return Err(invalid(op, &args));
                             };
                             Some(a.clone())
                         }
@@ -28,16 +42,15 @@ impl Foldable<String, Literal> for Instruction {
                         | ValueOps::Lt
                         | ValueOps::Le => {
                             let [Literal::Int(a), Literal::Int(b)] = args[..] else {
-                                panic!(
-                                    "Invalid arguments for binary arithmetic operation, expected 2 ints, got {:?}",
-                                    args
-                                );
+                                return Err(invalid(op, &args));
                             };
                             Some(match op {
-                                ValueOps::Add => Literal::Int(a + b),
-                                ValueOps::Sub => Literal::Int(a - b),
-                                ValueOps::Mul => Literal::Int(a * b),
-                                ValueOps::Div => Literal::Int(a / b),
+                                ValueOps::Add => Literal::Int(a.wrapping_add(b)),
+                                ValueOps::Sub => Literal::Int(a.wrapping_sub(b)),
+                                ValueOps::Mul => Literal::Int(a.wrapping_mul(b)),
+                                // Leave division by zero to fail at runtime
+                                ValueOps::Div if b == 0 => return Ok(Some((dest.clone(), None))),
+                                ValueOps::Div => Literal::Int(a.wrapping_div(b)),
                                 ValueOps::Eq => Literal::Bool(a == b),
                                 ValueOps::Gt => Literal::Bool(a > b),
                                 ValueOps::Ge => Literal::Bool(a >= b),
@@ -48,10 +61,7 @@ impl Foldable<String, Literal> for Instruction {
                         }
                         ValueOps::And | ValueOps::Or => {
                             let [Literal::Bool(a), Literal::Bool(b)] = args[..] else {
-                                panic!(
-                                    "Invalid arguments for binary boolean operation, expected 2 bools, got {:?}",
-                                    args
-                                );
+                                return Err(invalid(op, &args));
                             };
                             Some(Literal::Bool(match op {
                                 ValueOps::And => a && b,
@@ -61,10 +71,7 @@ impl Foldable<String, Literal> for Instruction {
                         }
                         ValueOps::Not => {
                             let [Literal::Bool(a)] = args[..] else {
-                                panic!(
-                                    "Invalid arguments for not operation, expected bool, got {:?}",
-                                    args
-                                );
+                                return Err(invalid(op, &args));
                             };
                             Some(Literal::Bool(!a))
                         }
@@ -78,10 +85,7 @@ impl Foldable<String, Literal> for Instruction {
                         | ValueOps::Flt
                         | ValueOps::Fle => {
                             let [Literal::Float(a), Literal::Float(b)] = args[..] else {
-                                panic!(
-                                    "Invalid arguments for binary arithmetic operation, expected 2 floats, got {:?}",
-                                    args
-                                );
+                                return Err(invalid(op, &args));
                             };
                             Some(match op {
                                 ValueOps::Fadd => Literal::Float(a + b),
@@ -102,10 +106,7 @@ impl Foldable<String, Literal> for Instruction {
                         | ValueOps::Clt
                         | ValueOps::Cle => {
                             let [Literal::Char(a), Literal::Char(b)] = args[..] else {
-                                panic!(
-                                    "Invalid arguments for binary arithmetic operation, expected 2 chars, got {:?}",
-                                    args
-                                );
+                                return Err(invalid(op, &args));
                             };
                             Some(match op {
                                 ValueOps::Ceq => Literal::Bool(a == b),
@@ -123,6 +124,6 @@ impl Foldable<String, Literal> for Instruction {
                 },
             )),
             Instruction::Effect { .. } => None,
-        }
+        })
     }
 }
@@ -1,3 +1,5 @@
+mod error;
+mod export;
 mod extensions;
 mod fold;
 mod graph;
@@ -8,6 +10,8 @@ mod passes;
 mod reps;
 
 pub use bril_rs;
+pub use error::{Error, ErrorKind, Location};
+pub use export::{OutputFormat, to_json};
 pub use extensions::{InstrExt, LiteralExt};
 pub use fold::Foldable;
 pub use graph::{GraphLike, draw};
@@ -15,8 +19,16 @@ pub use logger::{LogArgs, setup_logger, setup_logger_from_env};
 pub use misc::HashableLiteral;
 pub(crate) use passes::DataflowNode;
 pub use passes::{
-    AnalysisPass, CanonicalizeLiterals, Dataflow, DataflowLabel, DataflowPass, DominatorPass,
-    DominatorSetNode, FunctionPass, Pass, RemoveUnlabeledBlocks, draw_dataflow, run_analysis,
-    run_passes,
+    Analysis, AnalysisManager, AnalysisPass, CanonicalizeLiterals, Confluence, ConstProp,
+    Constants, Dataflow, DataflowLabel, DataflowPass, DataflowStats, DominatorPass,
+    DominatorSetNode, Effects, Flat, FunctionPass, FunctionSummary, GenKill, GenKillBuilder,
+    Lattice, LatticePass, Lifted, LiveVariables, MakeReducible, MapLattice, Numbering, Pass,
+    PassConstructor, PassRegistry, PowerSet, PreservedAnalyses, RemoveUnlabeledBlocks, SSAMode,
+    SSANames, SimplifyCFG, SplitCriticalEdges, Summaries, ToSSA, VerifySSA, WorklistOrder,
+    draw_dataflow, export_dataflow, run_analysis, run_passes, summarize,
+};
+pub use reps::{
+    BBFunction, BasicBlock, BitSet, CFG, CallGraph, ControlDependenceGraph, ControlFlow, DfsTree,
+    DominatorTree, EdgeClass, EdgeKind, FlowEdge, IrreducibleRegion, Loop, LoopForest,
+    PostDominatorTree,
 };
-pub use reps::{BBFunction, BasicBlock, CFG, CallGraph, ControlFlow, DominatorTree};
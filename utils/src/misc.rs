@@ -4,6 +4,7 @@ use std::{
 };
 
 use bril_rs::{Literal, Type};
+use serde::{Serialize, Serializer};
 
 /// Unsafe wrapper around a literal to allow hashing
 #[derive(PartialEq, Clone)]
@@ -56,6 +57,12 @@ impl hash::Hash for HashableLiteral {
 
 impl Eq for HashableLiteral {}
 
+impl Serialize for HashableLiteral {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.val.serialize(serializer)
+    }
+}
+
 impl Debug for HashableLiteral {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{:?}", self.val)
@@ -0,0 +1,168 @@
+use crate::{BBFunction, Error};
+use bril_rs::Function;
+use std::{
+    any::{Any, TypeId},
+    collections::{HashMap, HashSet},
+};
+
+/// An analysis whose results can be cached by an [AnalysisManager]
+pub trait Analysis: 'static {
+    type Result: 'static;
+
+    /// Compute the analysis for a function.
+    /// Other analyses this one depends on should be requested through the manager.
+    fn compute(am: &mut AnalysisManager, func: &BBFunction) -> Result<Self::Result, Error>;
+
+    /// Update a preserved result after the function it was computed from has changed,
+    /// and return whether it is still valid.
+    /// Useful for results that hold a copy of the function.
+    /// Results that are no longer valid are dropped, and computed again on next use.
+    fn refresh(_result: &mut Self::Result, _func: &BBFunction) -> bool {
+        true
+    }
+}
+
+/// Set of analyses that remain valid after a pass has run
+#[derive(Debug, Clone, Default)]
+pub struct PreservedAnalyses {
+    all: bool,
+    preserved: HashSet<TypeId>,
+}
+
+impl PreservedAnalyses {
+    /// No analyses are preserved
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    /// All analyses are preserved
+    pub fn all() -> Self {
+        Self {
+            all: true,
+            preserved: HashSet::new(),
+        }
+    }
+
+    /// Mark an analysis as preserved
+    pub fn preserve<A: Analysis>(mut self) -> Self {
+        self.preserved.insert(TypeId::of::<A>());
+        self
+    }
+
+    pub fn is_preserved<A: Analysis>(&self) -> bool {
+        self.contains(TypeId::of::<A>())
+    }
+
+    fn contains(&self, id: TypeId) -> bool {
+        self.all || self.preserved.contains(&id)
+    }
+}
+
+/// A type-erased cached analysis result
+struct CachedResult {
+    result: Box<dyn Any>,
+    refresh: fn(&mut dyn Any, &BBFunction) -> bool,
+}
+
+fn refresh_erased<A: Analysis>(result: &mut dyn Any, func: &BBFunction) -> bool {
+    let valid = result
+        .downcast_mut::<A::Result>()
+        .is_some_and(|result| A::refresh(result, func));
+
+    if !valid {
+        log::debug!(
+            "Preserved {} for @{} is out of date",
+            std::any::type_name::<A>(),
+            func.name
+        );
+    }
+
+    valid
+}
+
+/// Caches analysis results for each function of a program, keyed by function name.
+#[derive(Default)]
+pub struct AnalysisManager {
+    cache: HashMap<String, HashMap<TypeId, CachedResult>>,
+}
+
+impl AnalysisManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the result of an analysis on a function, computing it if it is not cached.
+    pub fn get<A: Analysis>(&mut self, func: &BBFunction) -> Result<&A::Result, Error> {
+        let id = TypeId::of::<A>();
+
+        let cached = self
+            .cache
+            .get(&func.name)
+            .is_some_and(|results| results.contains_key(&id));
+
+        if cached {
+            log::trace!(
+                "Using cached {} for @{}",
+                std::any::type_name::<A>(),
+                func.name
+            );
+        } else {
+            log::debug!(
+                "Computing {} for @{}",
+                std::any::type_name::<A>(),
+                func.name
+            );
+            let result = A::compute(self, func)?;
+            self.cache.entry(func.name.clone()).or_default().insert(
+                id,
+                CachedResult {
+                    result: Box::new(result),
+                    refresh: refresh_erased::<A>,
+                },
+            );
+        }
+
+        Ok(self.cache[&func.name][&id]
+            .result
+            .downcast_ref::<A::Result>()
+            .expect("Cached analysis result has the wrong type"))
+    }
+
+    /// Whether an analysis is currently cached for a function
+    pub fn is_cached<A: Analysis>(&self, name: &str) -> bool {
+        self.cache
+            .get(name)
+            .is_some_and(|results| results.contains_key(&TypeId::of::<A>()))
+    }
+
+    /// Invalidate the analyses of a function that were not preserved,
+    /// and refresh the preserved ones to refer to the updated function.
+    /// Preserved analyses that turn out to be out of date are invalidated as well.
+    pub fn invalidate(&mut self, func: &Function, preserved: &PreservedAnalyses) {
+        let Some(results) = self.cache.get_mut(&func.name) else {
+            return;
+        };
+
+        results.retain(|id, _| preserved.contains(*id));
+
+        if !results.is_empty() {
+            let bb_func = BBFunction::from(func.clone());
+            results.retain(|_, result| (result.refresh)(&mut *result.result, &bb_func));
+        }
+
+        if results.is_empty() {
+            self.cache.remove(&func.name);
+        }
+    }
+
+    /// Drop the analyses of functions that are no longer in the program
+    pub fn retain_functions(&mut self, funcs: &[Function]) {
+        self.cache
+            .retain(|name, _| funcs.iter().any(|func| &func.name == name));
+    }
+
+    /// Drop all cached results
+    pub fn clear(&mut self) {
+        self.cache.clear();
+    }
+}
@@ -1,4 +1,4 @@
-use bril_rs::{load_abstract_program_from_read, Function, Program};
+use bril_rs::{Function, Program, load_abstract_program_from_read};
 
 pub fn run_analysis(mut analysis: impl AnalysisPass) {
     let input = std::io::stdin();
@@ -1,18 +1,25 @@
-use crate::{BBFunction, BasicBlock, CFG, CallGraph, GraphLike, draw};
+use super::worklist::{Worklist, WorklistOrder, loop_heads};
+use crate::{BBFunction, BasicBlock, CFG, CallGraph, EdgeKind, Error, GraphLike, draw, to_json};
+use bril_rs::Instruction;
 use graphviz_rust::{
     dot_generator::{attr, id, node_id},
     dot_structures::{Attribute, Id, Node, NodeId, Stmt},
 };
 use itertools::Itertools;
+use serde::Serialize;
 use std::fmt::Debug;
-use std::{collections::LinkedList, fmt::Display};
+use std::fmt::Display;
 
 /// Results of a dataflow analysis
+#[derive(Serialize)]
 pub struct Dataflow<Val> {
     pub cfg: CFG,
     pub in_vals: Vec<Val>,
     pub out_vals: Vec<Val>,
     pub exit_val: Val,
+    /// Blocks that can never run, which are left at their initial values
+    /// and do not contribute to the values of other blocks
+    pub unreachable: Vec<usize>,
 }
 
 impl<F> Dataflow<F> {
@@ -25,10 +32,104 @@ impl<F> Dataflow<F> {
             in_vals: value.in_vals.into_iter().map(F::from).collect(),
             out_vals: value.out_vals.into_iter().map(F::from).collect(),
             exit_val: F::from(value.exit_val),
+            unreachable: value.unreachable,
         }
     }
 }
 
+impl<Val> Dataflow<Val> {
+    /// Convert every value of the results with `f`
+    pub fn map<U>(self, f: impl Fn(Val) -> U) -> Dataflow<U> {
+        Dataflow {
+            cfg: self.cfg,
+            in_vals: self.in_vals.into_iter().map(&f).collect(),
+            out_vals: self.out_vals.into_iter().map(&f).collect(),
+            exit_val: f(self.exit_val),
+            unreachable: self.unreachable,
+        }
+    }
+}
+
+impl<Val> Dataflow<Val>
+where
+    Val: Eq + Clone + Debug,
+{
+    /// Value at each point of a block, in program order.
+    /// The value at index `k` holds just before instruction `k`, and the last value holds after the block.
+    /// Replays the block with [DataflowPass::transfer_at], so `pass` must implement it.
+    pub fn instr_vals<Pass>(&self, pass: &Pass, block: usize) -> Vec<Val>
+    where
+        Pass: DataflowPass<Val> + ?Sized,
+    {
+        let bb = self.cfg.get(block);
+        let len = bb.iter().count();
+
+        let mut vals = Vec::with_capacity(len + 1);
+        vals.push(self.in_vals[block].clone());
+
+        if self.cfg.reversed() {
+            // The in value of a reversed block holds at its end
+            for idx in (0..len).rev() {
+                vals.push(replay(pass, bb, idx, vals.last().unwrap()));
+            }
+            vals.reverse();
+        } else {
+            for idx in 0..len {
+                vals.push(replay(pass, bb, idx, vals.last().unwrap()));
+            }
+        }
+
+        vals
+    }
+
+    /// Value just before instruction `idx` of a block, in program order.
+    /// Passing the number of instructions in the block gives the value after the block.
+    /// Replays the block with [DataflowPass::transfer_at], so `pass` must implement it.
+    pub fn at<Pass>(&self, pass: &Pass, block: usize, idx: usize) -> Val
+    where
+        Pass: DataflowPass<Val> + ?Sized,
+    {
+        let bb = self.cfg.get(block);
+        let len = bb.iter().count();
+        assert!(
+            idx <= len,
+            "Instruction {} out of bounds for block {}",
+            idx,
+            block
+        );
+
+        if self.cfg.reversed() {
+            (idx..len)
+                .rev()
+                .fold(self.in_vals[block].clone(), |val, i| {
+                    replay(pass, bb, i, &val)
+                })
+        } else {
+            (0..idx).fold(self.in_vals[block].clone(), |val, i| {
+                replay(pass, bb, i, &val)
+            })
+        }
+    }
+
+    /// Value just after instruction `idx` of a block, in program order.
+    pub fn after<Pass>(&self, pass: &Pass, block: usize, idx: usize) -> Val
+    where
+        Pass: DataflowPass<Val> + ?Sized,
+    {
+        self.at(pass, block, idx + 1)
+    }
+}
+
+/// Apply the instruction level transfer function of a pass
+fn replay<Pass, Val>(pass: &Pass, block: &BasicBlock, idx: usize, val: &Val) -> Val
+where
+    Val: Eq + Clone + Debug,
+    Pass: DataflowPass<Val> + ?Sized,
+{
+    pass.transfer_at(block, idx, val)
+        .expect("Dataflow pass does not implement transfer_instr or transfer_at")
+}
+
 impl<Val: PartialEq> PartialEq for Dataflow<Val> {
     fn eq(&self, other: &Self) -> bool {
         self.in_vals == other.in_vals
@@ -65,6 +166,36 @@ impl<Val: Debug> Debug for Dataflow<Val> {
     }
 }
 
+/// Statistics about a run of a dataflow analysis
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DataflowStats {
+    /// Number of calls to the block transfer function, including narrowing sweeps
+    pub transfers: usize,
+    /// Number of narrowing sweeps over the CFG
+    pub narrowing_sweeps: usize,
+    /// Number of times each block was transferred
+    pub visits: Vec<usize>,
+}
+
+impl DataflowStats {
+    /// Largest number of times a single block was transferred
+    pub fn max_visits(&self) -> usize {
+        self.visits.iter().copied().max().unwrap_or(0)
+    }
+}
+
+impl Display for DataflowStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} transfers, {} narrowing sweeps, at most {} visits per block",
+            self.transfers,
+            self.narrowing_sweeps,
+            self.max_visits()
+        )
+    }
+}
+
 /// Trait for dataflow analysis labels
 /// Allows conerting dataflow values to string labels
 pub trait DataflowLabel
@@ -151,6 +282,8 @@ where
             "blue"
         } else if node.is_exit() {
             "purple"
+        } else if self.unreachable.contains(&node.i) {
+            "gray"
         } else {
             "black"
         };
@@ -209,15 +342,90 @@ where
     /// Meet function
     fn meet(&self, in_vals: &[Val]) -> Val;
 
-    /// Transfer function
-    fn transfer(&self, block: &BasicBlock, in_val: &Val) -> Val;
+    /// Transfer function for a single instruction.
+    /// Returns [None] if the pass only works on whole blocks.
+    /// Passes implementing this get a block [DataflowPass::transfer] for free,
+    /// and can query values at each instruction with [Dataflow::at].
+    fn transfer_instr(&self, _instr: &Instruction, _val: &Val) -> Option<Val> {
+        None
+    }
+
+    /// Transfer function for instruction `idx` of a block, counting the control flow instruction.
+    /// Defaults to [DataflowPass::transfer_instr], passes whose effects depend on where
+    /// an instruction is rather than only on what it is, e.g. reaching definitions, override this instead.
+    fn transfer_at(&self, block: &BasicBlock, idx: usize, val: &Val) -> Option<Val> {
+        block
+            .iter()
+            .nth(idx)
+            .and_then(|instr| self.transfer_instr(instr, val))
+    }
+
+    /// Transfer function.
+    /// Defaults to applying [DataflowPass::transfer_instr] to each instruction,
+    /// in reverse if the pass is reversed.
+    fn transfer(&self, block: &BasicBlock, in_val: &Val) -> Val {
+        let apply = |val: Val, instr: &Instruction| {
+            self.transfer_instr(instr, &val)
+                .expect("Dataflow passes must implement either transfer or transfer_instr")
+        };
+
+        if self.reversed() {
+            block.iter().rev().fold(in_val.clone(), apply)
+        } else {
+            block.iter().fold(in_val.clone(), apply)
+        }
+    }
 
     /// Transfer function for the exit block
     fn finish(&self, _func: &BBFunction, exit_val: Val) -> Val {
         exit_val
     }
 
+    /// Transfer function for the edges of a branch, applied to the out value of `from` before it is merged into `to`.
+    /// Lets passes refine values with the branch condition, e.g. `cond` is true along [EdgeKind::True].
+    /// Edges are given in the direction of the analysis, so `to` branches to `from` for reversed passes.
+    fn edge_transfer(
+        &self,
+        _from: &BasicBlock,
+        _to: &BasicBlock,
+        _kind: EdgeKind,
+        val: Val,
+    ) -> Val {
+        val
+    }
+
+    /// Widening operator, applied to the in values of loop heads.
+    /// Passes over lattices with infinite ascending chains must override it so that the analysis terminates.
+    /// `prev` is the previous in value of the loop head, and `next` the newly merged one.
+    fn widen(&self, _prev: &Val, next: Val) -> Val {
+        next
+    }
+
+    /// Narrowing operator, applied to the in values of loop heads once a fixpoint is reached.
+    /// Used to recover some of the precision lost to [DataflowPass::widen].
+    fn narrow(&self, _prev: &Val, next: Val) -> Val {
+        next
+    }
+
+    /// Maximum number of narrowing sweeps over the CFG after the fixpoint is reached
+    fn narrowing_iters(&self) -> usize {
+        0
+    }
+
+    /// Order in which blocks are visited
+    fn worklist_order(&self) -> WorklistOrder {
+        WorklistOrder::default()
+    }
+
     fn cfg(&mut self, cfg: CFG) -> Dataflow<Val> {
+        let name = cfg.name().to_owned();
+        let (dataflow, stats) = self.cfg_with_stats(cfg);
+        log::debug!("Dataflow on @{} converged after {}", name, stats);
+        dataflow
+    }
+
+    /// Run the analysis, along with statistics about how long it took to converge
+    fn cfg_with_stats(&mut self, cfg: CFG) -> (Dataflow<Val>, DataflowStats) {
         let cfg = if cfg.reversed() != self.reversed() {
             cfg.reverse()
         } else {
@@ -225,22 +433,46 @@ where
         };
 
         let n = cfg.len();
+        let heads = loop_heads(&cfg);
+        let reachable = cfg.reachable();
+        let order = self.worklist_order();
+
+        let mut stats = DataflowStats {
+            visits: vec![0; n],
+            ..Default::default()
+        };
 
         let mut in_vals = vec![self.init(cfg.func()); n];
         let mut out_vals = vec![self.init(cfg.func()); n];
 
-        let mut worklist: LinkedList<_> = (0..n).collect();
-        while let Some(i) = worklist.pop_front() {
-            in_vals[i] = if cfg.is_entry(i) {
-                self.entry(cfg.func())
+        // Entry blocks also merge in the values of their predecessors, e.g. a loop back to the first block
+        let merge = |i: usize, out_vals: &[Val]| {
+            let inputs = cfg
+                .pred_edges(i)
+                .into_iter()
+                .filter(|&(j, _)| reachable[j])
+                .map(|(j, kind)| match kind {
+                    EdgeKind::True | EdgeKind::False => {
+                        self.edge_transfer(cfg.get(j), cfg.get(i), kind, out_vals[j].clone())
+                    }
+                    EdgeKind::Jump => out_vals[j].clone(),
+                })
+                .chain(cfg.is_entry(i).then(|| self.entry(cfg.func())))
+                .collect_vec();
+            log::trace!("Collected inputs for block {}: {:?}", i, inputs);
+            self.meet(&inputs)
+        };
+
+        let mut worklist = Worklist::new(order, &cfg, &reachable);
+        while let Some(i) = worklist.pop() {
+            stats.transfers += 1;
+            stats.visits[i] += 1;
+
+            let merged = merge(i, &out_vals);
+            in_vals[i] = if heads[i] {
+                self.widen(&in_vals[i], merged)
             } else {
-                let inputs = cfg
-                    .preds(i)
-                    .iter()
-                    .map(|&j| out_vals[j].clone())
-                    .collect_vec();
-                log::trace!("Collected inputs for block {}: {:?}", i, inputs);
-                self.meet(&inputs)
+                merged
             };
 
             log::trace!("Merged inputs for block {}: {:?}", i, in_vals[i]);
@@ -251,48 +483,113 @@ where
 
             if new_vals != out_vals[i] {
                 out_vals[i] = new_vals;
-                for j in cfg.succs(i) {
-                    worklist.push_back(j);
+                for j in cfg.succs(i).into_iter().filter(|&j| reachable[j]) {
+                    worklist.push(j);
+                }
+            }
+        }
+
+        // Sweep over the blocks again, narrowing at loop heads,
+        // until the values stop changing or we run out of iterations
+        let sweep = order
+            .blocks(&cfg)
+            .into_iter()
+            .filter(|&i| reachable[i])
+            .collect_vec();
+        for _ in 0..self.narrowing_iters() {
+            let mut changed = false;
+            stats.narrowing_sweeps += 1;
+
+            for &i in &sweep {
+                stats.transfers += 1;
+                stats.visits[i] += 1;
+
+                let merged = merge(i, &out_vals);
+                let new_in = if heads[i] {
+                    self.narrow(&in_vals[i], merged)
+                } else {
+                    merged
+                };
+                let new_out = self.transfer(cfg.func().get(i), &new_in);
+
+                if new_in != in_vals[i] || new_out != out_vals[i] {
+                    changed = true;
+                    in_vals[i] = new_in;
+                    out_vals[i] = new_out;
                 }
             }
+
+            if !changed {
+                break;
+            }
         }
 
-        // The exit value can be computed by meeting all the out values of exit block(s)
+        // The exit value can be computed by meeting all the out values of reachable exit block(s)
         let exit_val = cfg
             .exits()
             .into_iter()
+            .filter(|&i| reachable[i])
             .map(|i| out_vals[i].clone())
             .collect_vec();
         let exit_val = self.meet(&exit_val);
         let exit_val = self.finish(cfg.func(), exit_val);
 
-        Dataflow {
+        let unreachable = (0..n).filter(|&i| !reachable[i]).collect();
+
+        let dataflow = Dataflow {
             cfg,
             in_vals,
             out_vals,
             exit_val,
-        }
+            unreachable,
+        };
+
+        (dataflow, stats)
     }
 }
 
+/// Run a dataflow pass on every function of the program
+fn run_dataflow<Pass, Val, GraphNode>(
+    call_graph: &CallGraph,
+) -> Result<Vec<Dataflow<GraphNode>>, Error>
+where
+    Val: Eq + Clone + Debug,
+    Pass: DataflowPass<Val> + Default,
+    GraphNode: From<Val>,
+{
+    call_graph
+        .prog()
+        .functions
+        .iter()
+        .map(|f| {
+            let cfg = CFG::try_from(f.clone())?;
+            Ok(<Dataflow<GraphNode>>::from(Pass::default().cfg(cfg)))
+        })
+        .collect()
+}
+
 pub fn draw_dataflow<Pass, Val, GraphNode>(
     call_graph: CallGraph,
     directional: bool,
     strict: bool,
-) -> String
+) -> Result<String, Error>
 where
     Val: Eq + Clone + Debug,
     Pass: DataflowPass<Val> + Default,
     GraphNode: From<Val> + DataflowLabel,
     Dataflow<GraphNode>:,
 {
-    let results: Vec<_> = call_graph
-        .prog()
-        .functions
-        .iter()
-        .map(|f| Pass::default().cfg(CFG::from(f.clone())))
-        .map(<Dataflow<GraphNode>>::from)
-        .collect();
+    let results = run_dataflow::<Pass, Val, GraphNode>(&call_graph)?;
+    Ok(draw((call_graph, results), directional, strict))
+}
 
-    draw((call_graph, results), directional, strict)
+/// Run a dataflow pass on every function of the program and print the results as JSON,
+/// the machine-readable counterpart of [draw_dataflow]
+pub fn export_dataflow<Pass, Val, GraphNode>(call_graph: &CallGraph) -> Result<String, Error>
+where
+    Val: Eq + Clone + Debug,
+    Pass: DataflowPass<Val> + Default,
+    GraphNode: From<Val> + Serialize,
+{
+    Ok(to_json(&run_dataflow::<Pass, Val, GraphNode>(call_graph)?))
 }
@@ -1,22 +1,33 @@
-use crate::{BBFunction, BasicBlock};
+use crate::{BBFunction, BasicBlock, Error};
 
 pub trait FunctionPass {
-    fn basic_block(&mut self, bb: BasicBlock) -> BasicBlock {
-        bb
+    fn basic_block(&mut self, bb: BasicBlock) -> Result<BasicBlock, Error> {
+        Ok(bb)
     }
-    fn before(&mut self, func: BBFunction) -> BBFunction {
-        func
+    fn before(&mut self, func: BBFunction) -> Result<BBFunction, Error> {
+        Ok(func)
     }
-    fn after(&mut self, func: BBFunction) -> BBFunction {
-        func
+    fn after(&mut self, func: BBFunction) -> Result<BBFunction, Error> {
+        Ok(func)
     }
 
-    fn func(&mut self, func: BBFunction) -> BBFunction {
-        let func = self.before(func);
+    fn func(&mut self, func: BBFunction) -> Result<BBFunction, Error> {
+        let name = func.name.clone();
 
-        let func =
-            func.with_blocks(|blocks| blocks.into_iter().map(|bb| self.basic_block(bb)).collect());
+        let func = self.before(func).map_err(|e| e.in_func(&name))?;
 
-        self.after(func)
+        let func = func
+            .try_with_blocks(|blocks| {
+                blocks
+                    .into_iter()
+                    .map(|bb| {
+                        let label = bb.label_or_default().to_owned();
+                        self.basic_block(bb).map_err(|e| e.in_block(&label))
+                    })
+                    .collect()
+            })
+            .map_err(|e| e.in_func(&name))?;
+
+        self.after(func).map_err(|e| e.in_func(&name))
     }
 }
@@ -0,0 +1,329 @@
+use crate::{BBFunction, BasicBlock, BitSet, Confluence, Dataflow, DataflowPass};
+use bril_rs::Instruction;
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+};
+
+/// Assigns a dense index to each distinct item, e.g. the variables or definitions of a function
+#[derive(Debug, Clone)]
+pub struct Numbering<T> {
+    items: Vec<T>,
+    indices: HashMap<T, usize>,
+}
+
+impl<T: Clone + Eq + Hash> Numbering<T> {
+    pub fn new() -> Self {
+        Self {
+            items: vec![],
+            indices: HashMap::new(),
+        }
+    }
+
+    /// Index of an item, numbering it if it has not been seen yet
+    pub fn intern(&mut self, item: T) -> usize {
+        if let Some(&idx) = self.indices.get(&item) {
+            return idx;
+        }
+
+        self.items.push(item.clone());
+        self.indices.insert(item, self.items.len() - 1);
+        self.items.len() - 1
+    }
+
+    pub fn index(&self, item: &T) -> Option<usize> {
+        self.indices.get(item).copied()
+    }
+
+    pub fn get(&self, idx: usize) -> &T {
+        &self.items[idx]
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.items.iter()
+    }
+
+    /// Set of indices of the given items, which must all be numbered
+    pub fn encode<'a>(&self, items: impl IntoIterator<Item = &'a T>) -> BitSet
+    where
+        T: 'a,
+    {
+        let mut set = BitSet::new(self.len());
+        for item in items {
+            set.insert(self.indices[item]);
+        }
+        set
+    }
+
+    /// Items in a set of indices
+    pub fn decode(&self, set: &BitSet) -> HashSet<T> {
+        set.iter().map(|idx| self.items[idx].clone()).collect()
+    }
+}
+
+impl<T: Clone + Eq + Hash> Default for Numbering<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+type KillPredicate<'a, T> = Box<dyn Fn(&T) -> bool + 'a>;
+
+/// Items generated and killed by a single instruction, filled in by the closure given to [GenKillBuilder::build].
+/// Items are killed before they are generated, so an instruction can kill and regenerate the same item.
+pub struct Effects<'a, T> {
+    generated: Vec<T>,
+    killed: Vec<T>,
+    killed_where: Vec<KillPredicate<'a, T>>,
+}
+
+impl<'a, T> Effects<'a, T> {
+    fn new() -> Self {
+        Self {
+            generated: vec![],
+            killed: vec![],
+            killed_where: vec![],
+        }
+    }
+
+    /// The instruction generates an item
+    pub fn generates(&mut self, item: T) {
+        self.generated.push(item);
+    }
+
+    /// The instruction kills an item
+    pub fn kills(&mut self, item: T) {
+        self.killed.push(item);
+    }
+
+    /// The instruction kills every item matching `pred`, including items numbered after it
+    pub fn kills_where(&mut self, pred: impl Fn(&T) -> bool + 'a) {
+        self.killed_where.push(Box::new(pred));
+    }
+}
+
+/// Builder for a [GenKill] analysis
+pub struct GenKillBuilder<T> {
+    reversed: bool,
+    confluence: Confluence,
+    entry: Vec<T>,
+}
+
+impl<T: Clone + Eq + Hash> GenKillBuilder<T> {
+    /// Whether the analysis runs backwards, defaults to false
+    pub fn reversed(mut self, reversed: bool) -> Self {
+        self.reversed = reversed;
+        self
+    }
+
+    /// How values from predecessors are combined, defaults to union
+    pub fn confluence(mut self, confluence: Confluence) -> Self {
+        self.confluence = confluence;
+        self
+    }
+
+    /// Items in the value of the entry blocks
+    pub fn entry(mut self, items: impl IntoIterator<Item = T>) -> Self {
+        self.entry.extend(items);
+        self
+    }
+
+    /// Number the items of a function and compute the gen and kill sets of each block.
+    /// `effects` is called once for every instruction of the function.
+    pub fn build<'a>(
+        self,
+        func: &BBFunction,
+        effects: impl Fn(&BasicBlock, &Instruction, &mut Effects<'a, T>),
+    ) -> GenKill<T> {
+        let mut items = Numbering::new();
+        for item in &self.entry {
+            items.intern(item.clone());
+        }
+
+        // Collect the effects of every instruction first, as kill predicates
+        // need to see every item of the function
+        let block_effects: Vec<Vec<Effects<T>>> = func
+            .iter()
+            .map(|block| {
+                block
+                    .iter()
+                    .map(|instr| {
+                        let mut instr_effects = Effects::new();
+                        effects(block, instr, &mut instr_effects);
+
+                        for item in instr_effects.generated.iter().chain(&instr_effects.killed) {
+                            items.intern(item.clone());
+                        }
+
+                        instr_effects
+                    })
+                    .collect()
+            })
+            .collect();
+
+        // Encode the effects of each instruction once, evaluating the kill predicates
+        // against every item, so that blocks and single instructions can share them
+        let instr_sets: Vec<Vec<(BitSet, BitSet)>> = block_effects
+            .into_iter()
+            .map(|instr_effects| {
+                instr_effects
+                    .into_iter()
+                    .map(|effects| {
+                        let generated = items.encode(&effects.generated);
+                        let mut killed = items.encode(&effects.killed);
+                        for pred in &effects.killed_where {
+                            for (idx, item) in items.iter().enumerate() {
+                                if pred(item) {
+                                    killed.insert(idx);
+                                }
+                            }
+                        }
+                        (generated, killed)
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let n = items.len();
+        let (gens, kills) = instr_sets
+            .iter()
+            .map(|instr_sets| {
+                let mut block_gen = BitSet::new(n);
+                let mut block_kill = BitSet::new(n);
+
+                // Compose the effects of the instructions in the order they are applied
+                let mut compose = |(generated, killed): &(BitSet, BitSet)| {
+                    block_gen.difference_with(killed);
+                    block_gen.union_with(generated);
+                    block_kill.union_with(killed);
+                    block_kill.difference_with(generated);
+                };
+                if self.reversed {
+                    instr_sets.iter().rev().for_each(&mut compose);
+                } else {
+                    instr_sets.iter().for_each(&mut compose);
+                }
+
+                (block_gen, block_kill)
+            })
+            .unzip();
+
+        GenKill {
+            entry: items.encode(&self.entry),
+            items,
+            reversed: self.reversed,
+            confluence: self.confluence,
+            gens,
+            kills,
+            instr_sets,
+        }
+    }
+}
+
+/// A dataflow analysis whose transfer function removes a fixed set of killed items
+/// and adds a fixed set of generated items for each block.
+/// Items are numbered once per function, so values are dense [BitSet]s.
+pub struct GenKill<T> {
+    items: Numbering<T>,
+    reversed: bool,
+    confluence: Confluence,
+    entry: BitSet,
+    gens: Vec<BitSet>,
+    kills: Vec<BitSet>,
+    /// Items generated and killed by each instruction of each block, in program order
+    instr_sets: Vec<Vec<(BitSet, BitSet)>>,
+}
+
+impl<T: Clone + Eq + Hash> GenKill<T> {
+    pub fn builder() -> GenKillBuilder<T> {
+        GenKillBuilder {
+            reversed: false,
+            confluence: Confluence::Join,
+            entry: vec![],
+        }
+    }
+
+    /// Numbering of the items of the analysis
+    pub fn items(&self) -> &Numbering<T> {
+        &self.items
+    }
+
+    /// Items generated by a block
+    pub fn gen_set(&self, block: usize) -> &BitSet {
+        &self.gens[block]
+    }
+
+    /// Items killed by a block
+    pub fn kill_set(&self, block: usize) -> &BitSet {
+        &self.kills[block]
+    }
+
+    /// Decode the results of the analysis into sets of items
+    pub fn decode(&self, dataflow: Dataflow<BitSet>) -> Dataflow<HashSet<T>> {
+        dataflow.map(|set| self.items.decode(&set))
+    }
+}
+
+impl<T> GenKill<T> {
+    /// Identity of the confluence operator, the empty set for union and the full set for intersection
+    fn identity(&self) -> BitSet {
+        match self.confluence {
+            Confluence::Join => BitSet::new(self.entry.capacity()),
+            Confluence::Meet => BitSet::full(self.entry.capacity()),
+        }
+    }
+}
+
+impl<T> DataflowPass<BitSet> for GenKill<T> {
+    fn reversed(&self) -> bool {
+        self.reversed
+    }
+
+    fn entry(&self, _: &BBFunction) -> BitSet {
+        self.entry.clone()
+    }
+
+    fn init(&self, _: &BBFunction) -> BitSet {
+        self.identity()
+    }
+
+    fn meet(&self, in_vals: &[BitSet]) -> BitSet {
+        let mut vals = in_vals.iter();
+        let Some(first) = vals.next() else {
+            return self.identity();
+        };
+
+        let mut result = first.clone();
+        for val in vals {
+            match self.confluence {
+                Confluence::Join => result.union_with(val),
+                Confluence::Meet => result.intersect_with(val),
+            }
+        }
+        result
+    }
+
+    fn transfer_at(&self, block: &BasicBlock, idx: usize, val: &BitSet) -> Option<BitSet> {
+        let (generated, killed) = &self.instr_sets[block.idx][idx];
+        let mut val = val.clone();
+        val.difference_with(killed);
+        val.union_with(generated);
+        Some(val)
+    }
+
+    fn transfer(&self, block: &BasicBlock, in_val: &BitSet) -> BitSet {
+        let mut out_val = in_val.clone();
+        out_val.difference_with(&self.kills[block.idx]);
+        out_val.union_with(&self.gens[block.idx]);
+        out_val
+    }
+}
@@ -1,12 +1,12 @@
 use bril_rs::{Code, Instruction};
 
-use crate::{LiteralExt, Pass};
+use crate::{Error, LiteralExt, Pass, PreservedAnalyses};
 
 /// Pass to canonicalize literals to the right type
 pub struct CanonicalizeLiterals;
 
 impl Pass for CanonicalizeLiterals {
-    fn function(&mut self, mut func: bril_rs::Function) -> bril_rs::Function {
+    fn function(&mut self, mut func: bril_rs::Function) -> Result<bril_rs::Function, Error> {
         func.instrs = func
             .instrs
             .into_iter()
@@ -20,19 +20,26 @@ impl Pass for CanonicalizeLiterals {
                 }) = instr
                 {
                     // Make sure the literal matches the type of the destination
-                    Code::Instruction(Instruction::Constant {
-                        value: value.implicit_cast(&const_type),
+                    Ok(Code::Instruction(Instruction::Constant {
+                        value: value
+                            .implicit_cast(&const_type)
+                            .map_err(|e| e.at(pos.clone()))?,
                         const_type,
                         dest: dest.clone(),
                         op,
                         pos,
-                    })
+                    }))
                 } else {
-                    instr
+                    Ok(instr)
                 }
             })
-            .collect();
+            .collect::<Result<_, Error>>()?;
 
-        func
+        Ok(func)
+    }
+
+    fn preserved(&self) -> PreservedAnalyses {
+        // Only the types of literals change
+        PreservedAnalyses::all()
     }
 }
@@ -0,0 +1,119 @@
+use crate::{
+    BBFunction, BasicBlock, CFG, Dataflow, DataflowPass, EdgeKind, Error, Flat, Foldable,
+    HashableLiteral, InstrExt, Lattice, LatticePass, MapLattice, Summaries,
+};
+use bril_rs::{Instruction, Literal, ValueOps};
+use std::cell::RefCell;
+
+/// Constant value of each variable
+pub type Constants = MapLattice<String, Flat<HashableLiteral>>;
+
+/// Constant propagation.
+/// Calls produce unknown values, unless the pass is given the [Summaries] of the program,
+/// in which case the constant returned by the callee is used.
+/// Instructions that are ill-typed for the constants reaching them are errors, see [ConstProp::try_cfg].
+///
+/// Function arguments are unknown on entry, so they show up as top rather than being absent.
+/// Each instruction is folded with the constants computed by the instructions before it in the
+/// same block, so values computed earlier in a block are propagated within it
+/// (see `lessons/4/test/chain.bril`).
+#[derive(Default)]
+pub struct ConstProp<'a> {
+    summaries: Option<&'a Summaries>,
+    /// First instruction found to be ill-typed, which the transfer function cannot return
+    error: RefCell<Option<Error>>,
+}
+
+impl<'a> ConstProp<'a> {
+    /// Constant propagation that uses function summaries at call sites
+    pub fn with_summaries(summaries: &'a Summaries) -> Self {
+        Self {
+            summaries: Some(summaries),
+            ..Default::default()
+        }
+    }
+
+    /// Run constant propagation on a CFG.
+    /// Fails if an instruction is ill-typed for the constants that reach it.
+    pub fn try_cfg(&mut self, cfg: CFG) -> Result<Dataflow<Constants>, Error> {
+        let name = cfg.name().to_owned();
+        let constants = self.cfg(cfg);
+        match self.error.take() {
+            Some(e) => Err(e.in_func(&name)),
+            None => Ok(constants),
+        }
+    }
+}
+
+impl LatticePass<Constants> for ConstProp<'_> {
+    fn entry(&self, func: &BBFunction) -> Constants {
+        // Arguments can be anything
+        let mut val = Constants::bottom();
+        for arg in &func.args {
+            val.insert(arg.name.clone(), Flat::Top);
+        }
+        val
+    }
+
+    fn transfer_instr(&self, instr: &Instruction, val: &Constants) -> Option<Constants> {
+        let mut out_val = val.clone();
+
+        if let Instruction::Value {
+            op: ValueOps::Call,
+            dest,
+            funcs,
+            ..
+        } = instr
+            && let Some(summary) = funcs
+                .first()
+                .and_then(|f| self.summaries.and_then(|s| s.get(f)))
+        {
+            out_val.insert(dest.clone(), summary.returns.clone());
+            return Some(out_val);
+        }
+
+        // Fold with the constants right before this instruction, not at the start of the block
+        let folded = instr
+            .fold(|arg| val.get(arg).value().map(|c| c.clone().into()))
+            .unwrap_or_else(|e| {
+                // Keep going with an unknown value, the error is reported once the analysis is done
+                self.error.borrow_mut().get_or_insert(e);
+                instr.dest().map(|dest| (dest, None))
+            });
+
+        if let Some((dest, v)) = folded {
+            out_val.insert(
+                dest,
+                match v {
+                    Some(v) => Flat::Value(v.into()),
+                    None => Flat::Top,
+                },
+            );
+        }
+
+        Some(out_val)
+    }
+
+    fn edge_transfer(
+        &self,
+        from: &BasicBlock,
+        _: &BasicBlock,
+        kind: EdgeKind,
+        mut val: Constants,
+    ) -> Constants {
+        // The condition is known along each edge of a branch
+        let Some(cond) = from.branch_cond().map(str::to_owned) else {
+            return val;
+        };
+        let taken = Flat::Value(Literal::Bool(kind == EdgeKind::True).into());
+
+        match val.get(&cond) {
+            // The condition always goes the other way, so the edge is never taken
+            known @ Flat::Value(_) if *known != taken => Constants::bottom(),
+            _ => {
+                val.insert(cond, taken);
+                val
+            }
+        }
+    }
+}
@@ -1,41 +1,49 @@
-use crate::{BasicBlock, CFG, DataflowLabel, DataflowPass};
+use crate::{
+    BBFunction, BasicBlock, CFG, Confluence, DataflowLabel, Lattice, LatticePass, PowerSet,
+};
 use itertools::Itertools;
-use linked_hash_set::LinkedHashSet;
+use serde::{Serialize, Serializer};
+use std::collections::HashSet;
 
 /// Helper pass to calculate the dominators for a given CFG
 pub struct DominatorPass;
 
-impl DataflowPass<LinkedHashSet<usize>> for DominatorPass {
-    fn entry(&self, _: &crate::BBFunction) -> LinkedHashSet<usize> {
-        std::iter::once(0).collect()
+impl LatticePass<PowerSet<usize>> for DominatorPass {
+    fn confluence(&self) -> Confluence {
+        Confluence::Meet
     }
 
-    fn init(&self, func: &crate::BBFunction) -> LinkedHashSet<usize> {
-        (0..func.len()).collect()
+    fn entry(&self, _: &BBFunction) -> PowerSet<usize> {
+        std::iter::once(0).collect()
     }
 
-    fn meet(&self, in_vals: &[LinkedHashSet<usize>]) -> LinkedHashSet<usize> {
-        match in_vals {
-            [] => LinkedHashSet::new(),
-            [first] => first.clone(),
-            [first, rest @ ..] => rest.iter().fold(first.clone(), |acc, val| &acc & val),
-        }
+    fn init(&self, func: &BBFunction) -> PowerSet<usize> {
+        // Unreachable blocks keep their initial value, so it has to be a set that can be listed
+        (0..func.len()).collect()
     }
 
-    fn transfer(&self, block: &BasicBlock, in_val: &LinkedHashSet<usize>) -> LinkedHashSet<usize> {
+    fn transfer(&self, block: &BasicBlock, in_val: &PowerSet<usize>) -> PowerSet<usize> {
         // The dominators of a block are always the block itself
 
         let mut doms = in_val.clone();
         doms.insert(block.idx);
         doms
     }
+
+    fn finish(&self, _: &BBFunction, exit_val: PowerSet<usize>) -> PowerSet<usize> {
+        // Without a reachable exit there is nothing to meet, so the exit is left without dominators
+        match exit_val {
+            PowerSet::Full => PowerSet::bottom(),
+            doms => doms,
+        }
+    }
 }
 
 /// Dominator set node used to display graphs
 /// For dominator sets
 #[derive(Clone)]
 pub struct DominatorSetNode {
-    doms: LinkedHashSet<usize>,
+    doms: HashSet<usize>,
 }
 
 impl DataflowLabel for DominatorSetNode {
@@ -57,8 +65,19 @@ impl DataflowLabel for DominatorSetNode {
     }
 }
 
-impl From<LinkedHashSet<usize>> for DominatorSetNode {
-    fn from(doms: LinkedHashSet<usize>) -> Self {
+/// The dominators in index order
+impl Serialize for DominatorSetNode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.doms.iter().sorted_unstable())
+    }
+}
+
+impl From<PowerSet<usize>> for DominatorSetNode {
+    fn from(doms: PowerSet<usize>) -> Self {
+        // Every value computed by [DominatorPass] is finite
+        let PowerSet::Finite(doms) = doms else {
+            unreachable!("Dominator sets are never full")
+        };
         Self { doms }
     }
 }
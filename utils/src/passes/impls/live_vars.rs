@@ -0,0 +1,84 @@
+use crate::{
+    Analysis, AnalysisManager, BBFunction, CFG, Dataflow, DataflowPass, Error, GenKill, InstrExt,
+    Summaries,
+};
+use bril_rs::{EffectOps, Instruction, ValueOps};
+use std::collections::HashSet;
+
+/// Live variable analysis.
+/// Computes the set of variables that may be read before they are next written.
+#[derive(Default)]
+pub struct LiveVariables;
+
+impl LiveVariables {
+    /// Build the gen/kill analysis of the live variables of a function.
+    /// Each instruction kills its destination and generates its arguments.
+    pub fn gen_kill(func: &BBFunction) -> GenKill<String> {
+        Self::build(func, None)
+    }
+
+    /// Build the gen/kill analysis of the useful values of a function.
+    /// Unlike liveness, a call only generates the arguments its callee may read according to the
+    /// [Summaries] of the program, since the others can never affect its result.
+    pub fn useful(func: &BBFunction, summaries: &Summaries) -> GenKill<String> {
+        Self::build(func, Some(summaries))
+    }
+
+    fn build(func: &BBFunction, summaries: Option<&Summaries>) -> GenKill<String> {
+        GenKill::builder()
+            .reversed(true)
+            .build(func, |_, insn, effects| {
+                log::trace!("Processing instruction: {}", insn);
+
+                if let Some(dest) = insn.dest() {
+                    effects.kills(dest);
+                }
+
+                let callee = match insn {
+                    Instruction::Effect {
+                        op: EffectOps::Call,
+                        funcs,
+                        ..
+                    }
+                    | Instruction::Value {
+                        op: ValueOps::Call,
+                        funcs,
+                        ..
+                    } => funcs.first().and_then(|f| summaries?.get(f)),
+                    _ => None,
+                };
+
+                for (i, arg) in insn.args().into_iter().flatten().enumerate() {
+                    if callee.is_none_or(|summary| summary.reads_arg(i)) {
+                        effects.generates(arg);
+                    }
+                }
+            })
+    }
+
+    /// Run the analysis on a CFG
+    pub fn run(cfg: CFG) -> Dataflow<HashSet<String>> {
+        let mut analysis = Self::gen_kill(cfg.func());
+        let result = analysis.cfg(cfg);
+        analysis.decode(result)
+    }
+
+    /// Run the useful value analysis on a CFG
+    pub fn run_useful(cfg: CFG, summaries: &Summaries) -> Dataflow<HashSet<String>> {
+        let mut analysis = Self::useful(cfg.func(), summaries);
+        let result = analysis.cfg(cfg);
+        analysis.decode(result)
+    }
+}
+
+impl Analysis for LiveVariables {
+    type Result = Dataflow<HashSet<String>>;
+
+    fn compute(am: &mut AnalysisManager, func: &BBFunction) -> Result<Self::Result, Error> {
+        Ok(LiveVariables::run(am.get::<CFG>(func)?.clone()))
+    }
+
+    fn refresh(result: &mut Self::Result, func: &BBFunction) -> bool {
+        <CFG as Analysis>::refresh(&mut result.cfg, func)
+    }
+}
@@ -0,0 +1,15 @@
+use crate::{BBFunction, CFG, Error, Pass};
+
+/// Pass to make irreducible control flow reducible by copying blocks, see [CFG::make_reducible]
+pub struct MakeReducible;
+
+impl Pass for MakeReducible {
+    fn function(&mut self, func: bril_rs::Function) -> Result<bril_rs::Function, Error> {
+        let mut cfg = CFG::try_new(BBFunction::from(func))?;
+
+        let copied = cfg.make_reducible();
+        log::debug!("Copied {} blocks to make @{} reducible", copied, cfg.name());
+
+        Ok(cfg.into())
+    }
+}
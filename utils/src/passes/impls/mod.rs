@@ -1,7 +1,21 @@
 mod canonicalize_literals;
+mod const_prop;
 mod dominators;
+mod live_vars;
+mod make_reducible;
 mod remove_unlabeled;
+mod simplify_cfg;
+mod split_critical_edges;
+mod to_ssa;
+mod verify_ssa;
 
 pub use canonicalize_literals::CanonicalizeLiterals;
+pub use const_prop::{ConstProp, Constants};
 pub use dominators::{DominatorPass, DominatorSetNode};
+pub use live_vars::LiveVariables;
+pub use make_reducible::MakeReducible;
 pub use remove_unlabeled::RemoveUnlabeledBlocks;
+pub use simplify_cfg::SimplifyCFG;
+pub use split_critical_edges::SplitCriticalEdges;
+pub use to_ssa::{SSAMode, SSANames, ToSSA};
+pub use verify_ssa::VerifySSA;
@@ -1,10 +1,10 @@
-use crate::{BBFunction, Pass};
+use crate::{BBFunction, Error, Pass};
 
 /// Pass to canonicalize literals to the right type
 pub struct RemoveUnlabeledBlocks;
 
 impl Pass for RemoveUnlabeledBlocks {
-    fn function(&mut self, func: bril_rs::Function) -> bril_rs::Function {
+    fn function(&mut self, func: bril_rs::Function) -> Result<bril_rs::Function, Error> {
         let func = BBFunction::from(func);
 
         // Remove blocks that have Label = none that are not the entry block
@@ -15,6 +15,6 @@ impl Pass for RemoveUnlabeledBlocks {
                 .collect()
         });
 
-        func.into()
+        Ok(func.into())
     }
 }
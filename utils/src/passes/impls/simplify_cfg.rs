@@ -0,0 +1,117 @@
+use crate::{BBFunction, CFG, ConstProp, Error, FlowEdge, Pass};
+use bril_rs::Literal;
+use itertools::Itertools;
+
+/// Pass to clean up the CFG, repeating until nothing changes:
+/// - branches on constant conditions, or with the same target on both edges, become jumps
+/// - blocks that can never run are deleted
+/// - jumps to empty blocks are threaded through to the targets of those blocks
+/// - blocks are merged into their only predecessor when it has no other successor
+pub struct SimplifyCFG;
+
+impl Pass for SimplifyCFG {
+    fn function(&mut self, func: bril_rs::Function) -> Result<bril_rs::Function, Error> {
+        let mut cfg = CFG::try_new(BBFunction::from(func))?;
+
+        let mut rounds = 0;
+        loop {
+            rounds += 1;
+
+            let folded = fold_branches(&mut cfg)?;
+            let removed = cfg.remove_unreachable_blocks();
+            let threaded = thread_jumps(&mut cfg);
+            let merged = merge_blocks(&mut cfg);
+
+            log::debug!(
+                "Folded {} branches, removed {} blocks, threaded {} jumps and merged {} blocks in @{}",
+                folded,
+                removed,
+                threaded,
+                merged,
+                cfg.name()
+            );
+
+            if folded + removed + threaded + merged == 0 {
+                break;
+            }
+        }
+
+        log::debug!("Simplified @{} in {} round(s)", cfg.name(), rounds);
+
+        Ok(cfg.into())
+    }
+}
+
+/// Turn branches that always go the same way into jumps, and return the number of branches folded
+fn fold_branches(cfg: &mut CFG) -> Result<usize, Error> {
+    let constants = ConstProp::default().try_cfg(cfg.clone())?;
+
+    let folds = (0..cfg.len())
+        .filter_map(|idx| match cfg.flow_edge(idx) {
+            FlowEdge::Branch(t, f) if t == f => Some((idx, t)),
+            FlowEdge::Branch(t, f) => {
+                let cond = cfg.get(idx).branch_cond()?.to_owned();
+                match constants.out_vals[idx]
+                    .get(&cond)
+                    .value()
+                    .map(|c| Literal::from(c.clone()))
+                {
+                    Some(Literal::Bool(true)) => Some((idx, t)),
+                    Some(Literal::Bool(false)) => Some((idx, f)),
+                    _ => None,
+                }
+            }
+            _ => None,
+        })
+        .collect_vec();
+
+    for &(idx, target) in &folds {
+        cfg.set_jump(idx, target);
+    }
+
+    Ok(folds.len())
+}
+
+/// Redirect the edges into empty blocks that only jump elsewhere straight to their target,
+/// and return the number of edges threaded.
+/// The empty blocks are left without predecessors, so they are deleted in the next round.
+fn thread_jumps(cfg: &mut CFG) -> usize {
+    let mut threaded = 0;
+
+    // The entry block is kept, even if it is empty
+    for idx in 1..cfg.len() {
+        let FlowEdge::Jump(target) = cfg.flow_edge(idx) else {
+            continue;
+        };
+        if target == idx || !cfg.get(idx).is_empty() {
+            continue;
+        }
+
+        for pred in cfg.preds(idx).into_iter().unique() {
+            cfg.retarget(pred, idx, target);
+            threaded += 1;
+        }
+    }
+
+    threaded
+}
+
+/// Merge blocks into their only predecessor when it has no other successor, see [CFG::can_merge],
+/// and return the number of blocks merged
+fn merge_blocks(cfg: &mut CFG) -> usize {
+    let mut merged = 0;
+
+    let mut idx = 0;
+    while idx < cfg.len() {
+        match cfg.flow_edge(idx) {
+            // Keep merging into the same block, as it now has the successors of the merged one
+            FlowEdge::Jump(succ) if cfg.can_merge(idx, succ) => {
+                idx = cfg.merge_blocks(idx, succ);
+                merged += 1;
+            }
+            _ => idx += 1,
+        }
+    }
+
+    merged
+}
@@ -0,0 +1,16 @@
+use crate::{BBFunction, CFG, Error, Pass};
+
+/// Pass to split critical edges, i.e. edges from a block with several successors
+/// to a block with several predecessors, see [CFG::split_critical_edges]
+pub struct SplitCriticalEdges;
+
+impl Pass for SplitCriticalEdges {
+    fn function(&mut self, func: bril_rs::Function) -> Result<bril_rs::Function, Error> {
+        let mut cfg = CFG::try_new(BBFunction::from(func))?;
+
+        let split = cfg.split_critical_edges();
+        log::debug!("Split {} critical edges in @{}", split, cfg.name());
+
+        Ok(cfg.into())
+    }
+}
@@ -0,0 +1,472 @@
+use crate::{
+    AnalysisManager, BBFunction, CFG, DominatorTree, Error, InstrExt, LiveVariables, Pass,
+};
+use bril_rs::{Argument, EffectOps, Function, Instruction, Program, Type, ValueOps};
+use itertools::Itertools;
+use linked_hash_map::LinkedHashMap;
+use linked_hash_set::LinkedHashSet;
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+    vec,
+};
+
+#[derive(Debug, Default, Clone)]
+struct NameStack {
+    names: HashMap<String, Vec<String>>,
+}
+
+impl NameStack {
+    pub fn new(args: &[Argument]) -> Self {
+        let mut names = HashMap::new();
+        for arg in args {
+            names.insert(arg.name.clone(), vec![arg.name.clone()]);
+        }
+
+        Self { names }
+    }
+
+    pub fn get(&self, name: &str) -> Option<String> {
+        self.names.get(name).and_then(|v| v.last()).cloned()
+    }
+
+    pub fn push(&mut self, name: &str, new: String) -> String {
+        let entry = self.names.entry(name.to_owned()).or_default();
+
+        entry.push(new.clone());
+
+        new
+    }
+
+    /// Get the shadow name for a phi node
+    fn shadow_name(name: &str, bidx: usize) -> String {
+        format!("{}.{}.shadow", name, bidx)
+    }
+
+    /// Get a unique name for an instruction
+    fn unique_name(name: &str, bidx: usize, iidx: usize) -> String {
+        format!("{}.{}.{}", name, bidx, iidx)
+    }
+}
+
+/// Which variables get phi nodes at the dominance frontiers of their definitions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SSAMode {
+    /// Every variable written in a block, whether or not it is used afterwards
+    #[default]
+    Minimal,
+    /// Only variables that are read in some block before that block writes them,
+    /// so names that never leave the block they are defined in get no phi nodes
+    SemiPruned,
+    /// Only variables that are live on entry to the block the phi node would be placed in
+    Pruned,
+}
+
+impl FromStr for SSAMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "minimal" => Ok(Self::Minimal),
+            "semi-pruned" | "semi" => Ok(Self::SemiPruned),
+            "pruned" => Ok(Self::Pruned),
+            _ => Err(format!("Unknown SSA mode: {}", s)),
+        }
+    }
+}
+
+struct PhiNodes {
+    nodes: Vec<LinkedHashMap<String, Type>>,
+}
+
+impl PhiNodes {
+    pub fn new(doms: &DominatorTree, mode: SSAMode) -> Self {
+        let writes: Vec<LinkedHashMap<_, _>> = doms
+            .iter()
+            .map(|bb| {
+                bb.iter()
+                    .filter_map(|i| i.dest().and_then(|d| i.get_type().map(|t| (d, t))))
+                    .collect()
+            })
+            .collect();
+
+        // Map from variable to the blocks that write to it
+        let mut defs = LinkedHashMap::new();
+        for (block, writes) in writes.iter().enumerate() {
+            for (dest, ty) in writes {
+                defs.entry((dest.clone(), ty.clone()))
+                    .or_insert_with(LinkedHashSet::new)
+                    .insert(block);
+            }
+        }
+
+        let globals = (mode == SSAMode::SemiPruned).then(|| PhiNodes::globals(doms));
+        let live = (mode == SSAMode::Pruned).then(|| LiveVariables::run(doms.cfg().clone()));
+
+        // Whether a variable needs a phi node at the start of a block
+        let needs_phi = |var: &str, block: usize| match mode {
+            SSAMode::Minimal => true,
+            SSAMode::SemiPruned => globals.as_ref().unwrap().contains(var),
+            // The analysis runs backwards, so the out value of a block holds at its start
+            SSAMode::Pruned => live.as_ref().unwrap().out_vals[block].contains(var),
+        };
+
+        let mut nodes = vec![LinkedHashMap::new(); doms.len()];
+
+        for ((dest, ty), mut defs) in defs {
+            while let Some(d) = defs.pop_front() {
+                // The virtual exit node never needs a phi node
+                for block in doms
+                    .dominance_frontier(d)
+                    .iter()
+                    .filter(|&&b| b != doms.exit())
+                {
+                    if !needs_phi(&dest, *block) {
+                        continue;
+                    }
+
+                    if nodes[*block].insert(dest.clone(), ty.clone()).is_none() {
+                        // Newly inserted, add it to the defs
+                        defs.insert(*block);
+                    }
+                }
+            }
+        }
+
+        Self { nodes }
+    }
+
+    /// Variables read in some block before that block writes them.
+    /// These are the only names that can be live across blocks.
+    fn globals(doms: &DominatorTree) -> HashSet<String> {
+        let mut globals = HashSet::new();
+        for block in doms.iter() {
+            let mut written = HashSet::new();
+            for instr in block.iter() {
+                for arg in instr.args().into_iter().flatten() {
+                    if !written.contains(&arg) {
+                        globals.insert(arg);
+                    }
+                }
+
+                if let Some(dest) = instr.dest() {
+                    written.insert(dest);
+                }
+            }
+        }
+
+        globals
+    }
+
+    pub fn get(&self, block: usize) -> impl Iterator<Item = (&String, &Type)> {
+        self.nodes[block].iter()
+    }
+}
+
+/// Names given to the variables of a function by SSA construction
+#[derive(Debug, Default, Clone)]
+pub struct SSANames {
+    /// SSA names of each original variable, in the order they were created
+    versions: LinkedHashMap<String, Vec<String>>,
+    /// Original variable of each SSA name
+    originals: HashMap<String, String>,
+    /// Number of `get` instructions inserted
+    phis: usize,
+}
+
+impl SSANames {
+    fn add(&mut self, original: &str, name: &str) {
+        self.versions
+            .entry(original.to_owned())
+            .or_default()
+            .push(name.to_owned());
+        self.originals.insert(name.to_owned(), original.to_owned());
+    }
+
+    /// SSA names of a variable of the original function, including the shadow variables read by `get`.
+    /// Arguments keep their name, as do variables that had to be defined as `undef`.
+    pub fn versions(&self, original: &str) -> &[String] {
+        self.versions.get(original).map_or(&[], Vec::as_slice)
+    }
+
+    /// Variable of the original function that an SSA name is a version of
+    pub fn original(&self, name: &str) -> Option<&str> {
+        self.originals.get(name).map(String::as_str)
+    }
+
+    /// Number of phi nodes inserted, i.e. `get` instructions
+    pub fn phis(&self) -> usize {
+        self.phis
+    }
+
+    /// Each variable of the original function along with its SSA names
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &[String])> {
+        self.versions
+            .iter()
+            .map(|(original, versions)| (original.as_str(), versions.as_slice()))
+    }
+}
+
+/// Pass to convert a program into SSA form
+#[derive(Debug, Default)]
+pub struct ToSSA {
+    mode: SSAMode,
+    /// Number of phi nodes inserted in every function so far
+    phis: usize,
+    /// Whether to print the number of phi nodes to stderr once the pass has run
+    stats: bool,
+}
+
+impl ToSSA {
+    pub fn new(mode: SSAMode) -> Self {
+        Self {
+            mode,
+            ..Default::default()
+        }
+    }
+
+    /// Print the number of phi nodes inserted to stderr after the pass has run
+    pub fn with_stats(mut self, stats: bool) -> Self {
+        self.stats = stats;
+        self
+    }
+
+    /// Number of phi nodes inserted by the pass across every function it has run on
+    pub fn phis(&self) -> usize {
+        self.phis
+    }
+
+    /// Convert the function of a dominator tree into SSA form in place, and return the names given to its variables.
+    /// Fails if the entry block has predecessors, see [CFG::add_synthetic_entry],
+    /// and blocks that cannot be reached from the entry are left unchanged, see [CFG::remove_unreachable_blocks].
+    /// Only instructions are added and renamed, so the tree stays valid.
+    pub fn tree(&self, doms: &mut DominatorTree) -> Result<SSANames, Error> {
+        if !doms.is_empty() && !doms.cfg().preds(0).is_empty() {
+            // The entry would need phis for the values coming from its predecessors,
+            // but there is nowhere to set them on the way into the function
+            return Err(Error::other("the entry block has predecessors")
+                .in_func(doms.cfg().name())
+                .in_block(doms.get(0).label_or_default()));
+        }
+
+        let args = doms.cfg().func().args.clone();
+        let mut name_stack = NameStack::new(&args);
+        let mut names = SSANames::default();
+        for arg in &args {
+            names.add(&arg.name, &arg.name);
+        }
+
+        if doms.is_empty() {
+            return Ok(names);
+        }
+
+        let phi_nodes = PhiNodes::new(doms, self.mode);
+
+        let mut undefined = LinkedHashMap::new();
+
+        ToSSA::rename(
+            doms,
+            0,
+            &mut name_stack,
+            &phi_nodes,
+            &mut undefined,
+            &mut names,
+        )?;
+
+        // Add x: type = undef for all undefined variables
+        for (undef, ty) in undefined {
+            names.add(&undef, &undef);
+            let instr = Instruction::Value {
+                args: vec![],
+                dest: undef,
+                funcs: vec![],
+                labels: vec![],
+                op: ValueOps::Undef,
+                pos: None,
+                op_type: ty,
+            };
+
+            doms.get_mut(0).insert(0, instr);
+        }
+
+        Ok(names)
+    }
+
+    /// Convert a CFG into SSA form, and return its dominator tree along with the names given to its variables.
+    /// Blocks that cannot be reached from the entry are deleted first, and an entry block is added if needed.
+    pub fn cfg(&self, mut cfg: CFG) -> Result<(DominatorTree, SSANames), Error> {
+        cfg.remove_unreachable_blocks();
+        cfg.add_synthetic_entry();
+
+        let mut doms = DominatorTree::new(cfg);
+        let names = self.tree(&mut doms)?;
+        Ok((doms, names))
+    }
+
+    fn rename(
+        doms: &mut DominatorTree,
+        bidx: usize,
+        stack: &mut NameStack,
+        phi_nodes: &PhiNodes,
+        undefined: &mut LinkedHashMap<String, Type>,
+        names: &mut SSANames,
+    ) -> Result<(), Error> {
+        let old_stack = stack.clone();
+        log::info!("Renaming block {}", doms.get(bidx).label_or_default());
+        log::debug!("Stack: {:?}", stack);
+
+        // A use that no definition reaches has nothing to be renamed to
+        let func = doms.cfg().name().to_string();
+        let label = doms.get(bidx).label_or_default().to_string();
+        let undefined_use = |arg: &str, pos| {
+            Error::other(format!("use of undefined variable {}", arg))
+                .in_func(&func)
+                .in_block(&label)
+                .at(pos)
+        };
+
+        let block = doms.get_mut(bidx);
+        // Insert the get instructions
+        let gets = phi_nodes
+            .get(bidx)
+            .map(|(dst, ty)| {
+                let shadow = NameStack::shadow_name(dst, bidx);
+                names.add(dst, &shadow);
+                names.phis += 1;
+                stack.push(dst, shadow.clone());
+                Instruction::Value {
+                    dest: shadow,
+                    op: ValueOps::Get,
+                    args: vec![],
+                    funcs: vec![],
+                    labels: vec![],
+                    op_type: ty.clone(),
+                    pos: None,
+                }
+            })
+            .collect_vec();
+
+        for (i, instr) in block.iter_mut().enumerate() {
+            log::debug!("Renaming {}", instr);
+            match instr {
+                Instruction::Constant { dest, .. } => {
+                    let new = NameStack::unique_name(dest, bidx, i);
+                    names.add(dest, &new);
+                    stack.push(dest, new.clone());
+                    *dest = new;
+                }
+                Instruction::Value {
+                    args, dest, pos, ..
+                } => {
+                    for arg in args {
+                        *arg = stack
+                            .get(arg)
+                            .ok_or_else(|| undefined_use(arg, pos.clone()))?;
+                    }
+
+                    let new = NameStack::unique_name(dest, bidx, i);
+                    names.add(dest, &new);
+                    stack.push(dest, new.clone());
+                    *dest = new;
+                }
+                Instruction::Effect { args, pos, .. } => {
+                    for arg in args {
+                        *arg = stack
+                            .get(arg)
+                            .ok_or_else(|| undefined_use(arg, pos.clone()))?;
+                    }
+                }
+            }
+
+            log::debug!("Renamed to {}", instr);
+        }
+
+        // Insert the get instructions
+        for get in gets {
+            block.insert(0, get);
+        }
+
+        let phis = doms
+            .cfg()
+            .succs(bidx)
+            .into_iter()
+            .flat_map(|v| phi_nodes.get(v).map(move |(dst, ty)| (v, dst, ty)))
+            .map(|(succ, dst, ty)| {
+                let old_name = match stack.get(dst) {
+                    Some(name) => name,
+                    None => {
+                        // Add it to the undefined set
+                        undefined.insert(dst.clone(), ty.clone());
+                        dst.clone()
+                    }
+                };
+
+                Instruction::Effect {
+                    args: vec![NameStack::shadow_name(dst, succ), old_name],
+                    funcs: vec![],
+                    labels: vec![],
+                    op: EffectOps::Set,
+                    pos: None,
+                }
+            })
+            .collect_vec();
+
+        doms.get_mut(bidx).extend(phis);
+
+        // Rename all immediately dominated blocks
+        let exit = doms.exit();
+        for child in doms
+            .children(bidx)
+            .to_vec()
+            .into_iter()
+            .filter(|&c| c != exit)
+        {
+            ToSSA::rename(doms, child, stack, phi_nodes, undefined, names)?;
+        }
+
+        // Undo the stack changes
+        *stack = old_stack;
+        Ok(())
+    }
+}
+
+impl Pass for ToSSA {
+    fn function(&mut self, func: Function) -> Result<Function, Error> {
+        self.function_with(func, &mut AnalysisManager::new())
+    }
+
+    fn function_with(
+        &mut self,
+        func: Function,
+        am: &mut AnalysisManager,
+    ) -> Result<Function, Error> {
+        let mut cfg = CFG::try_new(BBFunction::from(func))?;
+
+        log::info!("Converting function {} to SSA", cfg.name());
+
+        // Unreachable blocks would never be renamed, and the entry needs a block of its own
+        // in case it has a `get`
+        let removed = cfg.remove_unreachable_blocks();
+        let added = cfg.add_synthetic_entry();
+
+        let mut doms = if removed == 0 && !added {
+            // The function is unchanged, so a cached dominator tree can be used
+            am.get::<DominatorTree>(cfg.func())?.clone()
+        } else {
+            DominatorTree::new(cfg)
+        };
+
+        let names = self.tree(&mut doms)?;
+        self.phis += names.phis();
+
+        Ok(doms.into())
+    }
+
+    fn after(&mut self, prog: Program) -> Result<Program, Error> {
+        if self.stats {
+            // Same format as the interpreter's profiling output, so brench can extract it
+            eprintln!("total_phis: {}", self.phis);
+        }
+        Ok(prog)
+    }
+}
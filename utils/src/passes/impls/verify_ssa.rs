@@ -0,0 +1,216 @@
+use crate::{AnalysisManager, BBFunction, DominatorTree, Error, InstrExt, Pass, PreservedAnalyses};
+use bril_rs::{EffectOps, Function, Instruction, ValueOps};
+use std::collections::{HashMap, HashSet};
+
+/// Pass to check that a program is in SSA form, leaving it unchanged.
+/// Can be placed between the stages of a pipeline to catch passes that break SSA form.
+///
+/// Besides every variable being assigned once, each use must be dominated by its definition,
+/// each `get` must have a matching `set` in every predecessor of its block,
+/// and each `set` must be read by a `get` in a successor of its block.
+pub struct VerifySSA;
+
+/// Where a variable is defined, as a block and the index of the instruction in it.
+/// Arguments are defined before the first instruction of the entry block.
+#[derive(Debug, Clone, Copy)]
+struct Def {
+    block: usize,
+    instr: Option<usize>,
+}
+
+impl VerifySSA {
+    /// Check the function of a dominator tree, returning an error naming the first offending instruction.
+    /// Blocks that cannot be reached from the entry are not checked.
+    pub fn tree(&self, doms: &DominatorTree) -> Result<(), Error> {
+        let cfg = doms.cfg();
+        let reachable = cfg.reachable();
+        let error = |msg: String, block: usize, instr: &Instruction| {
+            Error::other(format!("{}, in `{}`", msg, instr))
+                .in_block(cfg.get(block).label_or_default())
+                .at(instr.get_pos())
+        };
+
+        // Every variable must be assigned exactly once
+        let mut defs = HashMap::new();
+        for arg in &cfg.func().args {
+            defs.insert(
+                arg.name.clone(),
+                Def {
+                    block: 0,
+                    instr: None,
+                },
+            );
+        }
+        let mut gets = HashMap::new();
+        for (bidx, block) in cfg.iter().enumerate() {
+            for (iidx, instr) in block.iter().enumerate() {
+                let Some(dest) = instr.dest() else {
+                    continue;
+                };
+
+                let def = Def {
+                    block: bidx,
+                    instr: Some(iidx),
+                };
+                if defs.insert(dest.clone(), def).is_some() {
+                    return Err(error(
+                        format!("variable {} is assigned more than once", dest),
+                        bidx,
+                        instr,
+                    ));
+                }
+
+                if let Instruction::Value {
+                    op: ValueOps::Get, ..
+                } = instr
+                {
+                    gets.insert(dest, bidx);
+                }
+            }
+        }
+
+        let mut sets = vec![HashSet::new(); cfg.len()];
+        for (bidx, block) in cfg.iter().enumerate() {
+            if !reachable[bidx] {
+                continue;
+            }
+
+            for (iidx, instr) in block.iter().enumerate() {
+                let mut uses = instr.args().unwrap_or_default();
+
+                if let Instruction::Effect {
+                    op: EffectOps::Set,
+                    args,
+                    ..
+                } = instr
+                {
+                    let [shadow, _] = args.as_slice() else {
+                        return Err(error(
+                            format!("set expects 2 arguments, got {}", args.len()),
+                            bidx,
+                            instr,
+                        ));
+                    };
+
+                    // The shadow variable is not a use, it is only read by its `get`
+                    uses.remove(0);
+                    match gets.get(shadow) {
+                        None => {
+                            return Err(error(
+                                format!("set of {}, which is not read by any get", shadow),
+                                bidx,
+                                instr,
+                            ));
+                        }
+                        Some(&target) if !cfg.succs(bidx).contains(&target) => {
+                            return Err(error(
+                                format!(
+                                    "set of {}, which is read by a get in .{} that does not follow this block",
+                                    shadow,
+                                    cfg.get(target).label_or_default()
+                                ),
+                                bidx,
+                                instr,
+                            ));
+                        }
+                        Some(_) => {
+                            sets[bidx].insert(shadow.clone());
+                        }
+                    }
+                }
+
+                for var in uses {
+                    let Some(def) = defs.get(&var) else {
+                        return Err(error(
+                            format!("{} is used but never defined", var),
+                            bidx,
+                            instr,
+                        ));
+                    };
+
+                    if def.block == bidx {
+                        if def.instr.is_some_and(|d| d >= iidx) {
+                            return Err(error(
+                                format!("{} is used before its definition", var),
+                                bidx,
+                                instr,
+                            ));
+                        }
+                    } else if !doms.dominates(def.block, bidx) {
+                        return Err(error(
+                            format!(
+                                "{} is used, but its definition in .{} does not dominate this block",
+                                var,
+                                cfg.get(def.block).label_or_default()
+                            ),
+                            bidx,
+                            instr,
+                        ));
+                    }
+                }
+            }
+        }
+
+        // Every path into a `get` must have set its shadow variable
+        for (bidx, block) in cfg.iter().enumerate() {
+            if !reachable[bidx] {
+                continue;
+            }
+
+            for instr in block.iter() {
+                let Instruction::Value {
+                    op: ValueOps::Get,
+                    dest,
+                    ..
+                } = instr
+                else {
+                    continue;
+                };
+
+                let preds = cfg.preds(bidx);
+                if preds.is_empty() {
+                    return Err(error(
+                        format!("get of {} in a block without predecessors", dest),
+                        bidx,
+                        instr,
+                    ));
+                }
+
+                if let Some(&pred) = preds
+                    .iter()
+                    .find(|&&pred| reachable[pred] && !sets[pred].contains(dest))
+                {
+                    return Err(error(
+                        format!(
+                            "get of {} has no matching set in its predecessor .{}",
+                            dest,
+                            cfg.get(pred).label_or_default()
+                        ),
+                        bidx,
+                        instr,
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Pass for VerifySSA {
+    fn function_with(
+        &mut self,
+        func: Function,
+        am: &mut AnalysisManager,
+    ) -> Result<Function, Error> {
+        let doms = am.get::<DominatorTree>(&BBFunction::from(func.clone()))?;
+        self.tree(doms)?;
+
+        Ok(func)
+    }
+
+    fn preserved(&self) -> PreservedAnalyses {
+        // The program is never changed
+        PreservedAnalyses::all()
+    }
+}
@@ -0,0 +1,589 @@
+use crate::{BBFunction, BasicBlock, DataflowPass, EdgeKind, WorklistOrder};
+use bril_rs::Instruction;
+use itertools::Itertools;
+use serde::{Serialize, Serializer, ser::SerializeStruct};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{self, Debug, Display},
+    hash::Hash,
+};
+
+/// A bounded lattice of dataflow values
+pub trait Lattice: Clone + Eq + Debug {
+    /// The least element
+    fn bottom() -> Self;
+
+    /// The greatest element
+    fn top() -> Self;
+
+    /// Least upper bound
+    fn join(&self, other: &Self) -> Self;
+
+    /// Greatest lower bound
+    fn meet(&self, other: &Self) -> Self;
+
+    /// Partial order of the lattice
+    fn leq(&self, other: &Self) -> bool {
+        &self.join(other) == other
+    }
+
+    /// Widening of `self` by a newer value `next`.
+    /// Defaults to the join, which is only a valid widening for lattices of finite height.
+    fn widen(&self, next: &Self) -> Self {
+        self.join(next)
+    }
+
+    /// Narrowing of `self` by a newer value `next`, defaults to `next`
+    fn narrow(&self, next: &Self) -> Self {
+        next.clone()
+    }
+
+    /// Join a number of values, the join of nothing is bottom
+    fn join_all<'a>(vals: impl IntoIterator<Item = &'a Self>) -> Self
+    where
+        Self: 'a,
+    {
+        vals.into_iter()
+            .fold(Self::bottom(), |acc, val| acc.join(val))
+    }
+
+    /// Meet a number of values, the meet of nothing is top
+    fn meet_all<'a>(vals: impl IntoIterator<Item = &'a Self>) -> Self
+    where
+        Self: 'a,
+    {
+        vals.into_iter().fold(Self::top(), |acc, val| acc.meet(val))
+    }
+}
+
+/// How the values from multiple predecessors are combined
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Confluence {
+    /// Values are joined, for "may" analyses
+    Join,
+    /// Values are met, for "must" analyses
+    Meet,
+}
+
+/// A dataflow analysis over a [Lattice].
+/// Every [LatticePass] is a [DataflowPass], and only needs a transfer function.
+pub trait LatticePass<L: Lattice> {
+    /// Whether this dataflow pass is reversed
+    fn reversed(&self) -> bool {
+        false
+    }
+
+    /// How values from predecessors are combined
+    fn confluence(&self) -> Confluence {
+        Confluence::Join
+    }
+
+    /// Value at the entry block, bottom by default
+    fn entry(&self, _func: &BBFunction) -> L {
+        L::bottom()
+    }
+
+    /// Initial values of all blocks, the identity of the confluence by default
+    fn init(&self, _func: &BBFunction) -> L {
+        match self.confluence() {
+            Confluence::Join => L::bottom(),
+            Confluence::Meet => L::top(),
+        }
+    }
+
+    /// Transfer function for a single instruction, see [DataflowPass::transfer_instr]
+    fn transfer_instr(&self, _instr: &Instruction, _val: &L) -> Option<L> {
+        None
+    }
+
+    /// Transfer function, see [DataflowPass::transfer]
+    fn transfer(&self, block: &BasicBlock, in_val: &L) -> L {
+        let apply = |val: L, instr: &Instruction| {
+            self.transfer_instr(instr, &val)
+                .expect("Lattice passes must implement either transfer or transfer_instr")
+        };
+
+        if self.reversed() {
+            block.iter().rev().fold(in_val.clone(), apply)
+        } else {
+            block.iter().fold(in_val.clone(), apply)
+        }
+    }
+
+    /// Transfer function for the exit block
+    fn finish(&self, _func: &BBFunction, exit_val: L) -> L {
+        exit_val
+    }
+
+    /// Transfer function for branch edges, see [DataflowPass::edge_transfer]
+    fn edge_transfer(&self, _from: &BasicBlock, _to: &BasicBlock, _kind: EdgeKind, val: L) -> L {
+        val
+    }
+
+    /// Widening at loop heads, see [DataflowPass::widen].
+    /// No widening is done by default, passes can opt in with [Lattice::widen].
+    fn widen(&self, _prev: &L, next: L) -> L {
+        next
+    }
+
+    /// Narrowing at loop heads, see [DataflowPass::narrow]
+    fn narrow(&self, _prev: &L, next: L) -> L {
+        next
+    }
+
+    /// Number of narrowing sweeps, see [DataflowPass::narrowing_iters]
+    fn narrowing_iters(&self) -> usize {
+        0
+    }
+
+    /// Order in which blocks are visited, see [DataflowPass::worklist_order]
+    fn worklist_order(&self) -> WorklistOrder {
+        WorklistOrder::default()
+    }
+}
+
+impl<P, L> DataflowPass<L> for P
+where
+    P: LatticePass<L>,
+    L: Lattice,
+{
+    fn reversed(&self) -> bool {
+        LatticePass::reversed(self)
+    }
+
+    fn entry(&self, func: &BBFunction) -> L {
+        LatticePass::entry(self, func)
+    }
+
+    fn init(&self, func: &BBFunction) -> L {
+        LatticePass::init(self, func)
+    }
+
+    fn meet(&self, in_vals: &[L]) -> L {
+        match self.confluence() {
+            Confluence::Join => L::join_all(in_vals),
+            Confluence::Meet => L::meet_all(in_vals),
+        }
+    }
+
+    fn transfer_instr(&self, instr: &Instruction, val: &L) -> Option<L> {
+        LatticePass::transfer_instr(self, instr, val)
+    }
+
+    fn transfer(&self, block: &BasicBlock, in_val: &L) -> L {
+        LatticePass::transfer(self, block, in_val)
+    }
+
+    fn finish(&self, func: &BBFunction, exit_val: L) -> L {
+        LatticePass::finish(self, func, exit_val)
+    }
+
+    fn edge_transfer(&self, from: &BasicBlock, to: &BasicBlock, kind: EdgeKind, val: L) -> L {
+        LatticePass::edge_transfer(self, from, to, kind, val)
+    }
+
+    fn widen(&self, prev: &L, next: L) -> L {
+        LatticePass::widen(self, prev, next)
+    }
+
+    fn narrow(&self, prev: &L, next: L) -> L {
+        LatticePass::narrow(self, prev, next)
+    }
+
+    fn narrowing_iters(&self) -> usize {
+        LatticePass::narrowing_iters(self)
+    }
+
+    fn worklist_order(&self) -> WorklistOrder {
+        LatticePass::worklist_order(self)
+    }
+}
+
+/// Lattice of sets of items, ordered by inclusion.
+/// The full set is kept symbolic as the universe is usually not known.
+#[derive(Clone, PartialEq, Eq)]
+pub enum PowerSet<T: Eq + Hash> {
+    /// Set of all items
+    Full,
+    /// Set of finite items
+    Finite(HashSet<T>),
+}
+
+impl<T: Eq + Hash> PowerSet<T> {
+    pub fn empty() -> Self {
+        PowerSet::Finite(HashSet::new())
+    }
+
+    pub fn contains(&self, item: &T) -> bool {
+        match self {
+            PowerSet::Full => true,
+            PowerSet::Finite(set) => set.contains(item),
+        }
+    }
+
+    pub fn insert(&mut self, item: T) {
+        if let PowerSet::Finite(set) = self {
+            set.insert(item);
+        }
+    }
+
+    /// Remove all items not matching `f`.
+    /// Does nothing to the full set, as its items can not be enumerated.
+    pub fn retain(&mut self, f: impl FnMut(&T) -> bool) {
+        if let PowerSet::Finite(set) = self {
+            set.retain(f);
+        }
+    }
+}
+
+impl<T: Clone + Eq + Hash + Debug> Lattice for PowerSet<T> {
+    fn bottom() -> Self {
+        Self::empty()
+    }
+
+    fn top() -> Self {
+        PowerSet::Full
+    }
+
+    fn join(&self, other: &Self) -> Self {
+        match (self, other) {
+            (PowerSet::Full, _) | (_, PowerSet::Full) => PowerSet::Full,
+            (PowerSet::Finite(a), PowerSet::Finite(b)) => {
+                PowerSet::Finite(a.union(b).cloned().collect())
+            }
+        }
+    }
+
+    fn meet(&self, other: &Self) -> Self {
+        match (self, other) {
+            (PowerSet::Full, _) => other.clone(),
+            (_, PowerSet::Full) => self.clone(),
+            (PowerSet::Finite(a), PowerSet::Finite(b)) => {
+                PowerSet::Finite(a.intersection(b).cloned().collect())
+            }
+        }
+    }
+
+    fn leq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (_, PowerSet::Full) => true,
+            (PowerSet::Full, _) => false,
+            (PowerSet::Finite(a), PowerSet::Finite(b)) => a.is_subset(b),
+        }
+    }
+}
+
+impl<T: Eq + Hash> FromIterator<T> for PowerSet<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        PowerSet::Finite(iter.into_iter().collect())
+    }
+}
+
+impl<T: Eq + Hash + Debug> Debug for PowerSet<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PowerSet::Full => write!(f, "T"),
+            PowerSet::Finite(set) => write!(f, "{:?}", set),
+        }
+    }
+}
+
+/// Flat lattice of constants, where distinct constants are incomparable
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Flat<T> {
+    /// No value
+    Bottom,
+    /// A single known value
+    Value(T),
+    /// Any value
+    Top,
+}
+
+impl<T> Flat<T> {
+    pub fn value(&self) -> Option<&T> {
+        match self {
+            Flat::Value(v) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+impl<T: Clone + Eq + Debug> Lattice for Flat<T> {
+    fn bottom() -> Self {
+        Flat::Bottom
+    }
+
+    fn top() -> Self {
+        Flat::Top
+    }
+
+    fn join(&self, other: &Self) -> Self {
+        match (self, other) {
+            (Flat::Bottom, x) | (x, Flat::Bottom) => x.clone(),
+            (Flat::Value(a), Flat::Value(b)) if a == b => self.clone(),
+            _ => Flat::Top,
+        }
+    }
+
+    fn meet(&self, other: &Self) -> Self {
+        match (self, other) {
+            (Flat::Top, x) | (x, Flat::Top) => x.clone(),
+            (Flat::Value(a), Flat::Value(b)) if a == b => self.clone(),
+            _ => Flat::Bottom,
+        }
+    }
+}
+
+impl<T: Display> Display for Flat<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Flat::Bottom => write!(f, "⊥"),
+            Flat::Value(v) => v.fmt(f),
+            Flat::Top => write!(f, "T"),
+        }
+    }
+}
+
+/// A lattice with an extra bottom element added below `L`.
+/// Useful to distinguish unreached code from the bottom of the inner lattice.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Lifted<L> {
+    Bottom,
+    Lift(L),
+}
+
+impl<L: Lattice> Lattice for Lifted<L> {
+    fn bottom() -> Self {
+        Lifted::Bottom
+    }
+
+    fn top() -> Self {
+        Lifted::Lift(L::top())
+    }
+
+    fn join(&self, other: &Self) -> Self {
+        match (self, other) {
+            (Lifted::Bottom, x) | (x, Lifted::Bottom) => x.clone(),
+            (Lifted::Lift(a), Lifted::Lift(b)) => Lifted::Lift(a.join(b)),
+        }
+    }
+
+    fn meet(&self, other: &Self) -> Self {
+        match (self, other) {
+            (Lifted::Bottom, _) | (_, Lifted::Bottom) => Lifted::Bottom,
+            (Lifted::Lift(a), Lifted::Lift(b)) => Lifted::Lift(a.meet(b)),
+        }
+    }
+
+    fn leq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Lifted::Bottom, _) => true,
+            (_, Lifted::Bottom) => false,
+            (Lifted::Lift(a), Lifted::Lift(b)) => a.leq(b),
+        }
+    }
+
+    fn widen(&self, next: &Self) -> Self {
+        match (self, next) {
+            (Lifted::Lift(a), Lifted::Lift(b)) => Lifted::Lift(a.widen(b)),
+            _ => self.join(next),
+        }
+    }
+
+    fn narrow(&self, next: &Self) -> Self {
+        match (self, next) {
+            (Lifted::Lift(a), Lifted::Lift(b)) => Lifted::Lift(a.narrow(b)),
+            _ => next.clone(),
+        }
+    }
+}
+
+/// Lattice of maps to lattice values, ordered pointwise.
+/// Keys that are not in the map take the value `rest`,
+/// and entries equal to `rest` are never stored so that equal maps compare equal.
+#[derive(Clone, PartialEq, Eq)]
+pub struct MapLattice<K: Eq + Hash, V> {
+    map: HashMap<K, V>,
+    rest: V,
+}
+
+impl<K: Clone + Eq + Hash, V: Lattice> MapLattice<K, V> {
+    pub fn get(&self, key: &K) -> &V {
+        self.map.get(key).unwrap_or(&self.rest)
+    }
+
+    pub fn insert(&mut self, key: K, val: V) {
+        if val == self.rest {
+            self.map.remove(&key);
+        } else {
+            self.map.insert(key, val);
+        }
+    }
+
+    /// Entries that differ from the default value
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.map.iter()
+    }
+
+    /// Combine two maps pointwise
+    fn pointwise(&self, other: &Self, f: impl Fn(&V, &V) -> V) -> Self {
+        let mut result = Self {
+            map: HashMap::new(),
+            rest: f(&self.rest, &other.rest),
+        };
+
+        for key in self.map.keys().chain(other.map.keys()) {
+            result.insert(key.clone(), f(self.get(key), other.get(key)));
+        }
+
+        result
+    }
+}
+
+impl<K, V> Lattice for MapLattice<K, V>
+where
+    K: Clone + Eq + Hash + Debug,
+    V: Lattice,
+{
+    fn bottom() -> Self {
+        Self {
+            map: HashMap::new(),
+            rest: V::bottom(),
+        }
+    }
+
+    fn top() -> Self {
+        Self {
+            map: HashMap::new(),
+            rest: V::top(),
+        }
+    }
+
+    fn join(&self, other: &Self) -> Self {
+        self.pointwise(other, V::join)
+    }
+
+    fn meet(&self, other: &Self) -> Self {
+        self.pointwise(other, V::meet)
+    }
+
+    fn leq(&self, other: &Self) -> bool {
+        self.rest.leq(&other.rest)
+            && self
+                .map
+                .keys()
+                .chain(other.map.keys())
+                .all(|key| self.get(key).leq(other.get(key)))
+    }
+
+    fn widen(&self, next: &Self) -> Self {
+        self.pointwise(next, V::widen)
+    }
+
+    fn narrow(&self, next: &Self) -> Self {
+        self.pointwise(next, V::narrow)
+    }
+}
+
+impl<K: Eq + Hash + Debug, V: Debug> Debug for MapLattice<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(&self.map)
+            .entry(&"_", &self.rest)
+            .finish()
+    }
+}
+
+/// The stored entries sorted by key, so that the output is stable, along with the value of every other key
+impl<K: Eq + Hash + Ord + Serialize, V: Serialize> Serialize for MapLattice<K, V> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let entries = self
+            .map
+            .iter()
+            .sorted_unstable_by(|(a, _), (b, _)| a.cmp(b))
+            .collect::<Vec<_>>();
+
+        let mut state = serializer.serialize_struct("MapLattice", 2)?;
+        state.serialize_field("entries", &entries)?;
+        state.serialize_field("rest", &self.rest)?;
+        state.end()
+    }
+}
+
+impl<A: Lattice, B: Lattice> Lattice for (A, B) {
+    fn bottom() -> Self {
+        (A::bottom(), B::bottom())
+    }
+
+    fn top() -> Self {
+        (A::top(), B::top())
+    }
+
+    fn join(&self, other: &Self) -> Self {
+        (self.0.join(&other.0), self.1.join(&other.1))
+    }
+
+    fn meet(&self, other: &Self) -> Self {
+        (self.0.meet(&other.0), self.1.meet(&other.1))
+    }
+
+    fn leq(&self, other: &Self) -> bool {
+        self.0.leq(&other.0) && self.1.leq(&other.1)
+    }
+
+    fn widen(&self, next: &Self) -> Self {
+        (self.0.widen(&next.0), self.1.widen(&next.1))
+    }
+
+    fn narrow(&self, next: &Self) -> Self {
+        (self.0.narrow(&next.0), self.1.narrow(&next.1))
+    }
+}
+
+impl<A: Lattice, B: Lattice, C: Lattice> Lattice for (A, B, C) {
+    fn bottom() -> Self {
+        (A::bottom(), B::bottom(), C::bottom())
+    }
+
+    fn top() -> Self {
+        (A::top(), B::top(), C::top())
+    }
+
+    fn join(&self, other: &Self) -> Self {
+        (
+            self.0.join(&other.0),
+            self.1.join(&other.1),
+            self.2.join(&other.2),
+        )
+    }
+
+    fn meet(&self, other: &Self) -> Self {
+        (
+            self.0.meet(&other.0),
+            self.1.meet(&other.1),
+            self.2.meet(&other.2),
+        )
+    }
+
+    fn leq(&self, other: &Self) -> bool {
+        self.0.leq(&other.0) && self.1.leq(&other.1) && self.2.leq(&other.2)
+    }
+
+    fn widen(&self, next: &Self) -> Self {
+        (
+            self.0.widen(&next.0),
+            self.1.widen(&next.1),
+            self.2.widen(&next.2),
+        )
+    }
+
+    fn narrow(&self, next: &Self) -> Self {
+        (
+            self.0.narrow(&next.0),
+            self.1.narrow(&next.1),
+            self.2.narrow(&next.2),
+        )
+    }
+}
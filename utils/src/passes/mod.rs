@@ -1,12 +1,30 @@
+mod analysis_manager;
 mod analysis_pass;
 mod dataflow_pass;
 mod function_pass;
+mod gen_kill;
 mod impls;
+mod lattice;
 mod pass;
+mod registry;
+mod summaries;
+mod worklist;
 
+pub use analysis_manager::{Analysis, AnalysisManager, PreservedAnalyses};
 pub use analysis_pass::{AnalysisPass, run_analysis};
 pub(crate) use dataflow_pass::DataflowNode;
-pub use dataflow_pass::{Dataflow, DataflowLabel, DataflowPass, draw_dataflow};
+pub use dataflow_pass::{
+    Dataflow, DataflowLabel, DataflowPass, DataflowStats, draw_dataflow, export_dataflow,
+};
 pub use function_pass::FunctionPass;
-pub use impls::{CanonicalizeLiterals, DominatorPass, DominatorSetNode, RemoveUnlabeledBlocks};
+pub use gen_kill::{Effects, GenKill, GenKillBuilder, Numbering};
+pub use impls::{
+    CanonicalizeLiterals, ConstProp, Constants, DominatorPass, DominatorSetNode, LiveVariables,
+    MakeReducible, RemoveUnlabeledBlocks, SSAMode, SSANames, SimplifyCFG, SplitCriticalEdges,
+    ToSSA, VerifySSA,
+};
+pub use lattice::{Confluence, Flat, Lattice, LatticePass, Lifted, MapLattice, PowerSet};
 pub use pass::{Pass, run_passes};
+pub use registry::{PassConstructor, PassRegistry};
+pub use summaries::{FunctionSummary, Summaries, summarize};
+pub use worklist::WorklistOrder;
@@ -1,46 +1,104 @@
-use bril_rs::{load_abstract_program_from_read, output_program, Function, Program};
+use crate::{AnalysisManager, Error, ErrorKind, PreservedAnalyses};
+use bril_rs::{Function, Program, load_abstract_program_from_read, output_program};
+use std::collections::HashMap;
 
 pub fn run_passes(passes: &mut [Box<dyn Pass>]) {
     let input = std::io::stdin();
 
     // Read stdin and parse it into a Program using serde
-    let mut prog: Program = load_abstract_program_from_read(input.lock())
-        .try_into()
-        .unwrap();
+    let prog: Result<Program, _> = load_abstract_program_from_read(input.lock()).try_into();
 
-    // Run each pass on the program
-    for pass in passes {
-        prog = pass.run(prog);
-    }
+    let result = prog
+        .map_err(|e| Error::new(ErrorKind::Parse(e.to_string())))
+        .and_then(|mut prog| {
+            // Analyses are shared between passes so that preserved results are not recomputed
+            let mut am = AnalysisManager::new();
+
+            // Run each pass on the program
+            for pass in passes {
+                prog = pass.run_with(prog, &mut am)?;
+            }
 
-    output_program(&prog);
+            Ok(prog)
+        });
+
+    match result {
+        Ok(prog) => output_program(&prog),
+        Err(e) => {
+            e.report();
+            std::process::exit(1);
+        }
+    }
 }
 
 pub trait Pass {
     /// Function to be called on each function in the program
-    fn function(&mut self, func: Function) -> Function {
-        func
+    fn function(&mut self, func: Function) -> Result<Function, Error> {
+        Ok(func)
+    }
+
+    /// Function to be called on each function in the program, with access to cached analyses.
+    /// Defaults to [Pass::function].
+    fn function_with(
+        &mut self,
+        func: Function,
+        _am: &mut AnalysisManager,
+    ) -> Result<Function, Error> {
+        self.function(func)
+    }
+
+    /// Analyses that are still valid after this pass has run
+    fn preserved(&self) -> PreservedAnalyses {
+        PreservedAnalyses::none()
     }
 
     /// Function to be called before the pass is run
-    fn before(&mut self, prog: Program) -> Program {
-        prog
+    fn before(&mut self, prog: Program) -> Result<Program, Error> {
+        Ok(prog)
     }
 
     /// Function to be called after the pass is run
-    fn after(&mut self, prog: Program) -> Program {
-        prog
+    fn after(&mut self, prog: Program) -> Result<Program, Error> {
+        Ok(prog)
     }
 
-    fn run(&mut self, prog: Program) -> Program {
-        let mut prog = self.before(prog);
+    fn run(&mut self, prog: Program) -> Result<Program, Error> {
+        self.run_with(prog, &mut AnalysisManager::new())
+    }
+
+    /// Run the pass using and updating the analyses cached in `am`
+    fn run_with(&mut self, prog: Program, am: &mut AnalysisManager) -> Result<Program, Error> {
+        let preserved = self.preserved();
+
+        // Analyses can also be computed while the pass runs, so every function is compared afterwards
+        let original = prog
+            .functions
+            .iter()
+            .map(|func| (func.name.clone(), func.clone()))
+            .collect::<HashMap<_, _>>();
+
+        // Cached analyses are only updated once the pass has finished,
+        // so passes should not rely on the analyses of functions they have already changed.
+        let mut prog = self.before(prog)?;
 
         prog.functions = prog
             .functions
             .into_iter()
-            .map(|func| self.function(func))
-            .collect();
+            .map(|func| {
+                let name = func.name.clone();
+                self.function_with(func, am).map_err(|e| e.in_func(&name))
+            })
+            .collect::<Result<_, Error>>()?;
+
+        let prog = self.after(prog)?;
+
+        am.retain_functions(&prog.functions);
+        for func in &prog.functions {
+            if original.get(&func.name) != Some(func) {
+                am.invalidate(func, &preserved);
+            }
+        }
 
-        self.after(prog)
+        Ok(prog)
     }
 }
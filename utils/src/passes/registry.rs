@@ -0,0 +1,85 @@
+use crate::{
+    CanonicalizeLiterals, MakeReducible, Pass, RemoveUnlabeledBlocks, SSAMode, SimplifyCFG,
+    SplitCriticalEdges, ToSSA, VerifySSA,
+};
+use itertools::Itertools;
+use linked_hash_map::LinkedHashMap;
+
+/// Constructor for a pass in the registry
+pub type PassConstructor = fn() -> Box<dyn Pass>;
+
+/// Registry mapping pass names to their constructors.
+/// Allows building a pass pipeline from a list of names, e.g. `canonicalize,lvn,tdce`.
+pub struct PassRegistry {
+    passes: LinkedHashMap<String, PassConstructor>,
+}
+
+impl PassRegistry {
+    /// Create a registry containing all the passes defined in `utils`
+    pub fn new() -> Self {
+        let mut registry = Self::empty();
+        registry
+            .register("canonicalize", || Box::new(CanonicalizeLiterals))
+            .register("make-reducible", || Box::new(MakeReducible))
+            .register("remove-unlabeled", || Box::new(RemoveUnlabeledBlocks))
+            .register("simplify-cfg", || Box::new(SimplifyCFG))
+            .register("split-critical-edges", || Box::new(SplitCriticalEdges))
+            .register("to-ssa", || Box::new(ToSSA::default()))
+            .register("to-ssa-semi-pruned", || {
+                Box::new(ToSSA::new(SSAMode::SemiPruned))
+            })
+            .register("to-ssa-pruned", || Box::new(ToSSA::new(SSAMode::Pruned)))
+            .register("verify-ssa", || Box::new(VerifySSA));
+        registry
+    }
+
+    /// Create a registry without any passes
+    pub fn empty() -> Self {
+        Self {
+            passes: LinkedHashMap::new(),
+        }
+    }
+
+    /// Register a pass under a name, replacing any pass with the same name
+    pub fn register(&mut self, name: &str, ctor: PassConstructor) -> &mut Self {
+        self.passes.insert(name.to_owned(), ctor);
+        self
+    }
+
+    /// Construct a new instance of the pass with the given name
+    pub fn get(&self, name: &str) -> Option<Box<dyn Pass>> {
+        self.passes.get(name).map(|ctor| ctor())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.passes.contains_key(name)
+    }
+
+    /// Names of all registered passes, in registration order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.passes.keys().map(String::as_str)
+    }
+
+    /// Build a pipeline from a comma separated list of pass names
+    pub fn pipeline(&self, spec: &str) -> Result<Vec<Box<dyn Pass>>, String> {
+        spec.split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(|name| {
+                self.get(name).ok_or_else(|| {
+                    format!(
+                        "Unknown pass `{}`, expected one of: {}",
+                        name,
+                        self.names().join(", ")
+                    )
+                })
+            })
+            .collect()
+    }
+}
+
+impl Default for PassRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,119 @@
+use crate::{CFG, CallGraph, ConstProp, Error, Flat, HashableLiteral, Lattice, LiveVariables};
+use bril_rs::{EffectOps, Function, Instruction};
+use itertools::Itertools;
+use std::collections::HashMap;
+
+/// Facts about a function that hold at every call site
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionSummary {
+    /// Constant returned by the function, bottom if it never returns a value
+    pub returns: Flat<HashableLiteral>,
+    /// Whether each argument may be read by the function
+    pub reads_args: Vec<bool>,
+}
+
+impl FunctionSummary {
+    /// Most precise summary, which recursive functions start from
+    fn optimistic(func: &Function) -> Self {
+        Self {
+            returns: Flat::Bottom,
+            reads_args: vec![false; func.args.len()],
+        }
+    }
+
+    /// Whether the argument at `idx` may be read, extra arguments are assumed to be
+    pub fn reads_arg(&self, idx: usize) -> bool {
+        self.reads_args.get(idx).copied().unwrap_or(true)
+    }
+}
+
+/// Summaries of the functions of a program, by name
+pub type Summaries = HashMap<String, FunctionSummary>;
+
+/// Summarize every function of a program.
+/// The strongly connected components of the call graph are visited bottom-up so that callees are
+/// summarized before their callers, and the summaries of recursive components are iterated to a fixpoint.
+/// Fails if the CFG of a function cannot be built, or if constant propagation finds an ill-typed instruction.
+pub fn summarize(call_graph: &CallGraph) -> Result<Summaries, Error> {
+    let funcs = &call_graph.prog().functions;
+    let mut summaries = Summaries::new();
+
+    for scc in call_graph.sccs() {
+        let cfgs = scc
+            .iter()
+            .map(|&i| {
+                summaries.insert(
+                    funcs[i].name.clone(),
+                    FunctionSummary::optimistic(&funcs[i]),
+                );
+                CFG::try_from(funcs[i].clone())
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let recursive = call_graph.is_recursive(&scc);
+        let mut rounds = 0;
+        loop {
+            rounds += 1;
+            let mut changed = false;
+
+            for cfg in &cfgs {
+                let summary = summarize_function(cfg, &summaries)?;
+                let name = &cfg.func().name;
+                if summaries[name] != summary {
+                    changed = true;
+                    summaries.insert(name.clone(), summary);
+                }
+            }
+
+            if !recursive || !changed {
+                break;
+            }
+        }
+
+        log::debug!(
+            "Summarized {} in {} round(s)",
+            scc.iter()
+                .map(|&i| format!("@{}", funcs[i].name))
+                .join(", "),
+            rounds
+        );
+    }
+
+    Ok(summaries)
+}
+
+/// Summarize a function, using the current summaries of the functions it calls
+fn summarize_function(cfg: &CFG, summaries: &Summaries) -> Result<FunctionSummary, Error> {
+    let func = cfg.func();
+
+    let constants = ConstProp::with_summaries(summaries).try_cfg(cfg.clone())?;
+    let returns = func
+        .iter()
+        .filter_map(|block| match block.control_flow() {
+            Some(Instruction::Effect {
+                op: EffectOps::Return,
+                args,
+                ..
+            }) => args
+                .first()
+                .map(|arg| constants.out_vals[block.idx].get(arg).clone()),
+            _ => None,
+        })
+        .fold(Flat::bottom(), |acc, val| acc.join(&val));
+
+    let live = LiveVariables::run_useful(cfg.clone(), summaries);
+    let reads_args = func
+        .args
+        .iter()
+        .map(|arg| {
+            live.out_vals
+                .first()
+                .is_some_and(|vars| vars.contains(&arg.name))
+        })
+        .collect();
+
+    Ok(FunctionSummary {
+        returns,
+        reads_args,
+    })
+}
@@ -0,0 +1,107 @@
+use crate::{CFG, DominatorTree, EdgeClass, LoopForest};
+use itertools::Itertools;
+use std::collections::{BTreeSet, LinkedList};
+
+/// Order in which a dataflow analysis takes blocks off its worklist
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WorklistOrder {
+    /// Blocks are processed in the order they were added, starting in program order
+    Fifo,
+    /// Blocks earliest in reverse postorder come first.
+    /// As reversed passes run on the reversed CFG, this is the postorder of the function for them.
+    #[default]
+    ReversePostorder,
+    /// Blocks in the most deeply nested loops come first, then blocks earliest in reverse postorder.
+    /// Loops are the natural loops of [LoopForest], so irreducible cycles do not count.
+    LoopDepth,
+}
+
+impl WorklistOrder {
+    /// All blocks of a CFG, sorted by priority
+    pub fn blocks(&self, cfg: &CFG) -> Vec<usize> {
+        match self {
+            WorklistOrder::Fifo => (0..cfg.len()).collect(),
+            WorklistOrder::ReversePostorder => cfg.reverse_postorder(),
+            WorklistOrder::LoopDepth => {
+                // Loops are those of the function, so reversed CFGs are turned back first
+                let forward = if cfg.reversed() {
+                    cfg.clone().reverse()
+                } else {
+                    cfg.clone()
+                };
+                let loops = LoopForest::new(&DominatorTree::new(forward));
+                cfg.reverse_postorder()
+                    .into_iter()
+                    .sorted_by_key(|&i| std::cmp::Reverse(loops.depth(i)))
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Blocks left to visit in a dataflow analysis
+pub(crate) enum Worklist {
+    Fifo(LinkedList<usize>),
+    Priority {
+        /// Queued blocks, keyed by their rank
+        queue: BTreeSet<(usize, usize)>,
+        rank: Vec<usize>,
+    },
+}
+
+impl Worklist {
+    /// Create a worklist containing every reachable block of the CFG
+    pub fn new(order: WorklistOrder, cfg: &CFG, reachable: &[bool]) -> Self {
+        let blocks = order
+            .blocks(cfg)
+            .into_iter()
+            .filter(|&i| reachable[i])
+            .collect_vec();
+
+        match order {
+            WorklistOrder::Fifo => Worklist::Fifo(blocks.into_iter().collect()),
+            _ => {
+                let mut rank = vec![0; cfg.len()];
+                for (r, &i) in blocks.iter().enumerate() {
+                    rank[i] = r;
+                }
+
+                Worklist::Priority {
+                    queue: blocks.into_iter().map(|i| (rank[i], i)).collect(),
+                    rank,
+                }
+            }
+        }
+    }
+
+    pub fn push(&mut self, block: usize) {
+        match self {
+            Worklist::Fifo(list) => list.push_back(block),
+            Worklist::Priority { queue, rank } => {
+                queue.insert((rank[block], block));
+            }
+        }
+    }
+
+    pub fn pop(&mut self) -> Option<usize> {
+        match self {
+            Worklist::Fifo(list) => list.pop_front(),
+            Worklist::Priority { queue, .. } => queue.pop_first().map(|(_, block)| block),
+        }
+    }
+}
+
+/// Find the retreating edges of a CFG, i.e. the back edges of a depth first search from the entry.
+/// Blocks unreachable from the entry are searched afterwards so that all of their loops are also found.
+pub(crate) fn retreating_edges(cfg: &CFG) -> Vec<(usize, usize)> {
+    cfg.dfs().edges_of(EdgeClass::Back).collect()
+}
+
+/// Whether each block of a CFG is a loop head, i.e. the target of a retreating edge
+pub(crate) fn loop_heads(cfg: &CFG) -> Vec<bool> {
+    let mut heads = vec![false; cfg.len()];
+    for (_, head) in retreating_edges(cfg) {
+        heads[head] = true;
+    }
+    heads
+}
@@ -4,6 +4,7 @@ use graphviz_rust::{
     dot_generator::{attr, id},
     dot_structures::{Attribute, Id},
 };
+use serde::{Serialize, Serializer, ser::SerializeStruct};
 use std::fmt::{Debug, Display};
 
 /// Represents the final control flow of a basic block.
@@ -83,6 +84,18 @@ impl BasicBlock {
         self.control_flow.as_ref()
     }
 
+    /// Condition variable of the branch ending the block, if it ends with one
+    pub fn branch_cond(&self) -> Option<&str> {
+        match self.control_flow() {
+            Some(Instruction::Effect {
+                op: EffectOps::Branch,
+                args,
+                ..
+            }) => args.first().map(String::as_str),
+            _ => None,
+        }
+    }
+
     pub fn is_entry(&self) -> bool {
         self.idx == 0
     }
@@ -164,6 +177,27 @@ impl BasicBlock {
         }
     }
 
+    /// Remove the control flow instruction of the block, so that it falls through
+    pub fn take_control_flow(&mut self) -> Option<Instruction> {
+        self.control_flow.take()
+    }
+
+    /// Remove the instructions from index `at` onwards, including the control flow instruction
+    pub fn split_off(&mut self, at: usize) -> Vec<Instruction> {
+        let mut instrs = self.instrs.split_off(at);
+        instrs.extend(self.control_flow.take());
+        instrs
+    }
+
+    /// Make the control flow instruction of the block go to `new` wherever it went to `old`
+    pub fn retarget(&mut self, old: &str, new: &str) {
+        if let Some(Instruction::Effect { labels, .. }) = &mut self.control_flow {
+            for label in labels.iter_mut().filter(|label| *label == old) {
+                *label = new.to_owned();
+            }
+        }
+    }
+
     // Flatten the basic block into a vector of instructions.
     pub fn flatten(self) -> Vec<Code> {
         let mut instrs = match self.label {
@@ -201,3 +235,13 @@ impl Display for BasicBlock {
         Ok(())
     }
 }
+
+impl Serialize for BasicBlock {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("BasicBlock", 3)?;
+        state.serialize_field("index", &self.idx)?;
+        state.serialize_field("label", &self.label)?;
+        state.serialize_field("instrs", &self.iter().collect::<Vec<_>>())?;
+        state.end()
+    }
+}
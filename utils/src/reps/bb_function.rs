@@ -46,11 +46,68 @@ impl BBFunction {
         self.name_map.get(label).copied()
     }
 
+    /// A label that no block uses yet, based on `hint`
+    pub fn fresh_label(&self, hint: &str) -> String {
+        if !self.name_map.contains_key(hint) {
+            return hint.to_owned();
+        }
+
+        (1..)
+            .map(|i| format!("{}.{}", hint, i))
+            .find(|label| !self.name_map.contains_key(label))
+            .unwrap()
+    }
+
+    /// Relabel a block, keeping the name map consistent.
+    /// Jumps and branches to the old label are not updated.
+    pub fn set_label(&mut self, idx: usize, label: String) {
+        if let Some(old) = self.blocks[idx].label.replace(label.clone()) {
+            self.name_map.remove(&old);
+        }
+        self.name_map.insert(label, idx);
+    }
+
+    /// Add a block at the end of the function and return its index.
+    /// The previous last block no longer falls off the end of the function,
+    /// so its control flow should be made explicit first.
+    pub fn push_block(&mut self, label: Option<String>, instrs: Vec<Instruction>) -> usize {
+        let idx = self.blocks.len();
+        if let Some(label) = &label {
+            self.name_map.insert(label.clone(), idx);
+        }
+        self.blocks.push(BasicBlock::new(idx, label, instrs));
+        idx
+    }
+
+    /// Remove a block, shifting the blocks after it down by one index
+    pub fn remove_block(&mut self, idx: usize) -> BasicBlock {
+        let removed = self.blocks.remove(idx);
+
+        self.name_map.clear();
+        for (idx, block) in self.blocks.iter_mut().enumerate() {
+            block.idx = idx;
+            if let Some(label) = &block.label {
+                self.name_map.insert(label.clone(), idx);
+            }
+        }
+
+        removed
+    }
+
     /// Create a new [BBFunction] using this one but with a new set of blocks.
     /// Often useful in passes that add or remove new blocks.
     /// Makes sure the name map stays consistent.
     pub fn with_blocks(self, f: impl FnOnce(Vec<BasicBlock>) -> Vec<BasicBlock>) -> Self {
-        let mut blocks = f(self.blocks);
+        let Ok(func) = self.try_with_blocks(|blocks| Ok::<_, std::convert::Infallible>(f(blocks)));
+        func
+    }
+
+    /// Fallible version of [BBFunction::with_blocks].
+    pub fn try_with_blocks<E>(
+        self,
+        f: impl FnOnce(Vec<BasicBlock>) -> Result<Vec<BasicBlock>, E>,
+    ) -> Result<Self, E> {
+        let mut blocks = f(self.blocks)?;
 
         let mut name_map = HashMap::new();
         // Reconstruct the name map and idxs of each block
@@ -61,13 +118,13 @@ impl BBFunction {
             }
         }
 
-        Self {
+        Ok(Self {
             name: self.name,
             args: self.args,
             blocks,
             return_type: self.return_type,
             name_map,
-        }
+        })
     }
 }
 
@@ -0,0 +1,117 @@
+use std::fmt::{self, Debug};
+
+const WORD_BITS: usize = u64::BITS as usize;
+
+/// Dense set of indices below a fixed capacity
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct BitSet {
+    words: Vec<u64>,
+    capacity: usize,
+}
+
+impl BitSet {
+    /// Empty set of indices below `capacity`
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            words: vec![0; capacity.div_ceil(WORD_BITS)],
+            capacity,
+        }
+    }
+
+    /// Set of all indices below `capacity`
+    pub fn full(capacity: usize) -> Self {
+        let mut set = Self {
+            words: vec![u64::MAX; capacity.div_ceil(WORD_BITS)],
+            capacity,
+        };
+
+        // Clear the bits past the capacity so that equal sets compare equal
+        if !capacity.is_multiple_of(WORD_BITS)
+            && let Some(last) = set.words.last_mut()
+        {
+            *last = (1 << (capacity % WORD_BITS)) - 1;
+        }
+
+        set
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Add an index to the set, returns whether it was not already present
+    pub fn insert(&mut self, idx: usize) -> bool {
+        assert!(idx < self.capacity, "Index {} out of bounds", idx);
+        let (word, bit) = (idx / WORD_BITS, 1 << (idx % WORD_BITS));
+        let absent = self.words[word] & bit == 0;
+        self.words[word] |= bit;
+        absent
+    }
+
+    /// Remove an index from the set, returns whether it was present
+    pub fn remove(&mut self, idx: usize) -> bool {
+        if idx >= self.capacity {
+            return false;
+        }
+        let (word, bit) = (idx / WORD_BITS, 1 << (idx % WORD_BITS));
+        let present = self.words[word] & bit != 0;
+        self.words[word] &= !bit;
+        present
+    }
+
+    pub fn contains(&self, idx: usize) -> bool {
+        idx < self.capacity && self.words[idx / WORD_BITS] & (1 << (idx % WORD_BITS)) != 0
+    }
+
+    /// Number of indices in the set
+    pub fn count(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|&w| w == 0)
+    }
+
+    /// Add all the indices of `other`, both sets must have the same capacity
+    pub fn union_with(&mut self, other: &Self) {
+        self.zip_with(other, |a, b| a | b);
+    }
+
+    /// Keep only the indices also in `other`, both sets must have the same capacity
+    pub fn intersect_with(&mut self, other: &Self) {
+        self.zip_with(other, |a, b| a & b);
+    }
+
+    /// Remove all the indices of `other`, both sets must have the same capacity
+    pub fn difference_with(&mut self, other: &Self) {
+        self.zip_with(other, |a, b| a & !b);
+    }
+
+    fn zip_with(&mut self, other: &Self, f: impl Fn(u64, u64) -> u64) {
+        assert_eq!(
+            self.capacity, other.capacity,
+            "Bit sets have different capacities"
+        );
+        for (a, &b) in self.words.iter_mut().zip(&other.words) {
+            *a = f(*a, b);
+        }
+    }
+
+    /// Indices in the set, in increasing order
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words.iter().enumerate().flat_map(|(i, &word)| {
+            // Repeatedly clear the lowest set bit of the word
+            std::iter::successors((word != 0).then_some(word), |&w| {
+                let w = w & (w - 1);
+                (w != 0).then_some(w)
+            })
+            .map(move |w| i * WORD_BITS + w.trailing_zeros() as usize)
+        })
+    }
+}
+
+impl Debug for BitSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
@@ -5,6 +5,7 @@ use graphviz_rust::{
     dot_structures::{Attribute, Edge, EdgeTy, Id, NodeId, Stmt, Subgraph, Vertex},
 };
 use itertools::Itertools;
+use serde::{Serialize, Serializer, ser::SerializeStruct};
 use std::collections::HashMap;
 
 /// Call graph of the program
@@ -91,19 +92,98 @@ impl CallGraph {
     pub fn get(&self, name: &str) -> &Function {
         &self.prog.functions[self.idx_map[name]]
     }
+
+    /// Strongly connected components of the call graph, found with Tarjan's algorithm.
+    /// Components are in bottom-up order, i.e. a function comes after every function it calls
+    /// unless they are in the same component.
+    pub fn sccs(&self) -> Vec<Vec<usize>> {
+        let n = self.len();
+        let mut index = vec![None; n];
+        let mut lowlink = vec![0; n];
+        let mut on_stack = vec![false; n];
+        let mut stack = vec![];
+        let mut sccs = vec![];
+        let mut next_index = 0;
+
+        for root in 0..n {
+            if index[root].is_some() {
+                continue;
+            }
+
+            // Explicit stack of (function, index of the next callee to visit)
+            let mut calls = vec![(root, 0)];
+            index[root] = Some(next_index);
+            lowlink[root] = next_index;
+            next_index += 1;
+            stack.push(root);
+            on_stack[root] = true;
+
+            while let Some(&(node, next)) = calls.last() {
+                if let Some(&succ) = self.succs[node].get(next) {
+                    calls.last_mut().unwrap().1 += 1;
+
+                    match index[succ] {
+                        None => {
+                            index[succ] = Some(next_index);
+                            lowlink[succ] = next_index;
+                            next_index += 1;
+                            stack.push(succ);
+                            on_stack[succ] = true;
+                            calls.push((succ, 0));
+                        }
+                        Some(succ_index) if on_stack[succ] => {
+                            lowlink[node] = lowlink[node].min(succ_index);
+                        }
+                        Some(_) => {}
+                    }
+                    continue;
+                }
+
+                calls.pop();
+                if let Some(&(parent, _)) = calls.last() {
+                    lowlink[parent] = lowlink[parent].min(lowlink[node]);
+                }
+
+                // The node is the root of a component
+                if index[node] == Some(lowlink[node]) {
+                    let mut scc = vec![];
+                    while let Some(member) = stack.pop() {
+                        on_stack[member] = false;
+                        scc.push(member);
+                        if member == node {
+                            break;
+                        }
+                    }
+                    scc.reverse();
+                    sccs.push(scc);
+                }
+            }
+        }
+
+        sccs
+    }
+
+    /// Whether the functions of a strongly connected component can call themselves
+    pub fn is_recursive(&self, scc: &[usize]) -> bool {
+        match scc {
+            [func] => self.succs[*func].contains(func),
+            _ => !scc.is_empty(),
+        }
+    }
 }
 
-impl<'bb, SG> GraphLike<SG> for CallGraph
+/// A call graph along with the subgraph of each of its functions
+impl<'bb, SG> GraphLike<&SG> for (CallGraph, Vec<SG>)
 where
-    SG: From<Function> + GraphLike<&'bb BasicBlock>,
+    SG: GraphLike<&'bb BasicBlock>,
 {
-    fn node(&self, gid: &[usize], node: SG, id: usize) -> Stmt {
+    fn node(&self, gid: &[usize], node: &SG, id: usize) -> Stmt {
         let new_gid = gid.iter().chain([id].iter()).copied().collect::<Vec<_>>();
 
         Subgraph {
             id: id!(&format!(
                 "{}_wrapper",
-                <Self as GraphLike<SG>>::graph_id(self, &new_gid)
+                <Self as GraphLike<&SG>>::graph_id(self, &new_gid)
             )),
             stmts: vec![
                 attr!("peripheries", 0).into(),
@@ -119,23 +199,23 @@ where
     }
 
     fn graph_nodes(&self, gid: &[usize]) -> Vec<Stmt> {
-        self.prog
-            .functions
+        self.1
             .iter()
             .enumerate()
-            .map(|(i, bb)| self.node(gid, SG::from(bb.clone()), i))
+            .map(|(i, sg)| self.node(gid, sg, i))
             .collect()
     }
 
     fn graph_edges(&self, gid: &[usize]) -> Vec<Stmt> {
-        self.succs
+        self.0
+            .succs
             .iter()
             .enumerate()
             .flat_map(|(i, succs)| {
                 succs.iter().map(move |&j| {
                     // Because of the limitations of graphviz cluster subgraphs, we need to generate the edges between the exit and entry nodes
-                    let src_cluster = <Self as GraphLike<SG>>::node_id(self, gid, i).0;
-                    let dst_cluster = <Self as GraphLike<SG>>::node_id(self, gid, j).0;
+                    let src_cluster = <Self as GraphLike<&SG>>::node_id(self, gid, i).0;
+                    let dst_cluster = <Self as GraphLike<&SG>>::node_id(self, gid, j).0;
 
                     let src_exit = format!("{}_0", src_cluster);
                     let dst_entry = format!("{}_0", dst_cluster);
@@ -153,6 +233,35 @@ where
     }
 }
 
+/// Call of one function by another as it appears in JSON
+#[derive(Serialize)]
+struct JsonCall<'a> {
+    caller: &'a str,
+    callee: &'a str,
+}
+
+/// The names of the functions and the calls between them
+impl Serialize for CallGraph {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let funcs = &self.prog.functions;
+        let calls = funcs
+            .iter()
+            .zip(&self.succs)
+            .flat_map(|(caller, callees)| {
+                callees.iter().map(|&callee| JsonCall {
+                    caller: &caller.name,
+                    callee: &funcs[callee].name,
+                })
+            })
+            .collect_vec();
+
+        let mut state = serializer.serialize_struct("CallGraph", 2)?;
+        state.serialize_field("functions", &funcs.iter().map(|f| &f.name).collect_vec())?;
+        state.serialize_field("calls", &calls)?;
+        state.end()
+    }
+}
+
 impl GraphLike<Function> for CallGraph {
     fn node_attrs(&self, node: Function) -> Vec<Attribute> {
         vec![
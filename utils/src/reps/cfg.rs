@@ -1,17 +1,39 @@
-use crate::{BBFunction, BasicBlock, GraphLike};
+use crate::{
+    Analysis, AnalysisManager, BBFunction, BasicBlock, ControlFlow, DfsTree, EdgeClass, Error,
+    ErrorKind, GraphLike, IrreducibleRegion,
+};
 use bril_rs::{EffectOps, Function, Instruction};
 use graphviz_rust::{
     dot_generator::{attr, edge, id, node, node_id},
     dot_structures::{Attribute, Edge, EdgeTy, Id, Node, NodeId, Stmt, Vertex},
 };
+use itertools::Itertools;
+use serde::{Serialize, Serializer, ser::SerializeStruct};
+use std::collections::HashMap;
 
+/// Outgoing control flow of a block
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum FlowEdge {
+pub enum FlowEdge {
+    /// The block returns or falls off the end of the function
     Exit,
+    /// The block branches to its true and false targets
     Branch(usize, usize),
+    /// The block jumps or falls through to a single target
     Jump(usize),
 }
 
+/// Kind of a single edge between two blocks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EdgeKind {
+    /// Taken when the branch condition is true
+    True,
+    /// Taken when the branch condition is false
+    False,
+    /// Unconditional jump or fall through
+    Jump,
+}
+
 impl FlowEdge {
     pub fn vec(&self) -> Vec<usize> {
         match self {
@@ -20,6 +42,24 @@ impl FlowEdge {
             FlowEdge::Jump(j) => vec![*j],
         }
     }
+
+    /// Targets of the edge along with their kinds
+    pub fn edges(&self) -> Vec<(usize, EdgeKind)> {
+        match self {
+            FlowEdge::Exit => vec![],
+            FlowEdge::Branch(t, f) => vec![(*t, EdgeKind::True), (*f, EdgeKind::False)],
+            FlowEdge::Jump(j) => vec![(*j, EdgeKind::Jump)],
+        }
+    }
+
+    /// Apply `f` to each target of the edge
+    pub fn map(self, f: impl Fn(usize) -> usize) -> Self {
+        match self {
+            FlowEdge::Exit => FlowEdge::Exit,
+            FlowEdge::Branch(t, e) => FlowEdge::Branch(f(t), f(e)),
+            FlowEdge::Jump(j) => FlowEdge::Jump(f(j)),
+        }
+    }
 }
 /// Control Flow Graph representation.
 #[derive(Debug, Clone)]
@@ -31,46 +71,71 @@ pub struct CFG {
 }
 
 impl CFG {
+    /// Build the CFG of a function.
+    /// Panics if a jump or branch refers to a label that does not exist, see [CFG::try_new].
     pub fn new(func: BBFunction) -> Self {
+        Self::try_new(func).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Build the CFG of a function, failing if a jump or branch refers to a label that does not exist.
+    pub fn try_new(func: BBFunction) -> Result<Self, Error> {
         let n: usize = func.len();
 
-        let succs: Vec<_> = func
+        let succs = func
             .iter()
             .map(|block| {
                 // Branch/Return/Jump Instruction handling
-                block.control_flow().map(|instr| {
-                    log::trace!("Block {} has control flow instruction {}", block.idx, instr);
-                    match instr {
-                        Instruction::Effect {
-                            op: op @ (EffectOps::Jump | EffectOps::Branch),
-                            labels,
-                            ..
-                        } => {
-                            let mut labels = labels.iter().map(|l| {
-                                func.get_block_idx(l).unwrap_or_else(|| {
-                                    panic!("Label {} not found", l);
+                block
+                    .control_flow()
+                    .map(|instr| {
+                        log::trace!("Block {} has control flow instruction {}", block.idx, instr);
+                        match instr {
+                            Instruction::Effect {
+                                op: op @ (EffectOps::Jump | EffectOps::Branch),
+                                labels,
+                                pos,
+                                ..
+                            } => {
+                                let labels = labels
+                                    .iter()
+                                    .map(|l| {
+                                        func.get_block_idx(l).ok_or_else(|| {
+                                            Error::new(ErrorKind::UnknownLabel(l.clone()))
+                                                .in_func(&func.name)
+                                                .in_block(block.label_or_default())
+                                                .at(pos.clone())
+                                        })
+                                    })
+                                    .collect::<Result<Vec<_>, _>>()?;
+
+                                Ok(match (op, &labels[..]) {
+                                    (EffectOps::Jump, [target]) => FlowEdge::Jump(*target),
+                                    (EffectOps::Branch, [t, f]) => FlowEdge::Branch(*t, *f),
+                                    _ => {
+                                        return Err(Error::other(format!(
+                                            "{} expects {} labels, got {}",
+                                            op,
+                                            if *op == EffectOps::Jump { 1 } else { 2 },
+                                            labels.len()
+                                        ))
+                                        .in_func(&func.name)
+                                        .in_block(block.label_or_default())
+                                        .at(pos.clone()));
+                                    }
                                 })
-                            });
-
-                            match op {
-                                EffectOps::Jump => FlowEdge::Jump(labels.next().unwrap()),
-                                EffectOps::Branch => {
-                                    FlowEdge::Branch(labels.next().unwrap(), labels.next().unwrap())
-                                }
-                                _ => unreachable!(),
                             }
+                            Instruction::Effect {
+                                op: EffectOps::Return,
+                                ..
+                            } => Ok(FlowEdge::Exit),
+                            _ => unreachable!("Expected control flow instruction"),
                         }
-                        Instruction::Effect {
-                            op: EffectOps::Return,
-                            ..
-                        } => FlowEdge::Exit,
-                        _ => unreachable!("Expected control flow instruction"),
-                    }
-                })
+                    })
+                    .transpose()
             })
             .enumerate()
             .map(|(i, succs)| {
-                if let Some(s) = succs {
+                Ok(if let Some(s) = succs? {
                     // If the block has a branch/return/jump instruction, return the labels
                     s
                 } else if i + 1 < n {
@@ -79,9 +144,9 @@ impl CFG {
                 } else {
                     // Final block has no successors
                     FlowEdge::Exit
-                }
+                })
             })
-            .collect();
+            .collect::<Result<Vec<_>, Error>>()?;
 
         let mut preds = vec![vec![]; n];
         for (i, succs) in succs.iter().enumerate() {
@@ -97,12 +162,12 @@ impl CFG {
             }
         }
 
-        Self {
+        Ok(Self {
             func,
             preds,
             succs,
             reversed: false,
-        }
+        })
     }
 
     pub fn name(&self) -> &str {
@@ -143,14 +208,21 @@ impl CFG {
         self.func.iter_mut()
     }
 
-    /// Check whether a block idx is an entry block (no predecessors)
+    /// Check whether a block idx is an entry block.
+    /// The first block is the only entry of the function, even if it has predecessors,
+    /// while the entries of a reversed CFG are the blocks that exit the function.
     pub fn is_entry(&self, idx: usize) -> bool {
         match self.reversed {
             true => matches!(self.succs[idx], FlowEdge::Exit),
-            false => self.preds[idx].is_empty(),
+            false => idx == 0,
         }
     }
 
+    /// Entry blocks of the CFG, see [CFG::is_entry]
+    pub fn entries(&self) -> Vec<usize> {
+        (0..self.len()).filter(|&i| self.is_entry(i)).collect()
+    }
+
     pub fn preds(&self, idx: usize) -> Vec<usize> {
         match self.reversed {
             true => self.succs[idx].vec(),
@@ -165,12 +237,122 @@ impl CFG {
         }
     }
 
+    /// Predecessors of a block along with the kind of the edge connecting them.
+    /// A block branching to the same target on both edges appears twice.
+    pub fn pred_edges(&self, idx: usize) -> Vec<(usize, EdgeKind)> {
+        match self.reversed {
+            true => self.succs[idx].edges(),
+            false => self.forward_pred_edges(idx),
+        }
+    }
+
+    /// Successors of a block along with the kind of the edge connecting them
+    pub fn succ_edges(&self, idx: usize) -> Vec<(usize, EdgeKind)> {
+        match self.reversed {
+            true => self.forward_pred_edges(idx),
+            false => self.succs[idx].edges(),
+        }
+    }
+
+    /// Outgoing control flow of a block, ignoring whether the CFG is reversed
+    pub fn flow_edge(&self, idx: usize) -> FlowEdge {
+        self.succs[idx]
+    }
+
+    fn forward_pred_edges(&self, idx: usize) -> Vec<(usize, EdgeKind)> {
+        let mut preds = self.preds[idx].clone();
+        preds.dedup();
+
+        preds
+            .into_iter()
+            .flat_map(|pred| {
+                self.succs[pred]
+                    .edges()
+                    .into_iter()
+                    .filter(|&(succ, _)| succ == idx)
+                    .map(move |(_, kind)| (pred, kind))
+            })
+            .collect()
+    }
+
+    /// Depth first spanning forest of the CFG, see [DfsTree]
+    pub fn dfs(&self) -> DfsTree {
+        DfsTree::new(self)
+    }
+
+    /// Blocks in preorder of a depth first search from the entry blocks.
+    /// Blocks unreachable from the entry are searched afterwards, so every block is included.
+    pub fn preorder(&self) -> Vec<usize> {
+        self.dfs().preorder().to_vec()
+    }
+
+    /// Blocks in postorder of a depth first search from the entry blocks, see [CFG::preorder]
+    pub fn postorder(&self) -> Vec<usize> {
+        self.dfs().postorder().to_vec()
+    }
+
+    /// Blocks in reverse postorder of a depth first search from the entry blocks, see [CFG::preorder]
+    pub fn reverse_postorder(&self) -> Vec<usize> {
+        self.dfs().reverse_postorder()
+    }
+
+    /// Class of every edge with respect to a depth first search from the entry blocks
+    pub fn classify_edges(&self) -> Vec<(usize, usize, EdgeClass)> {
+        self.dfs().edges().to_vec()
+    }
+
     pub fn exits(&self) -> Vec<usize> {
         (0..self.len())
             .filter(|&i| self.succs[i] == FlowEdge::Exit)
             .collect()
     }
 
+    /// Whether each block can be reached from the first block of the function.
+    /// Edges are followed in the direction of control flow, even if the CFG is reversed,
+    /// so these are the blocks that may run.
+    pub fn reachable(&self) -> Vec<bool> {
+        let mut reachable = vec![false; self.len()];
+        let mut stack = if self.is_empty() { vec![] } else { vec![0] };
+        while let Some(block) = stack.pop() {
+            if !reachable[block] {
+                reachable[block] = true;
+                stack.extend(self.succs[block].vec());
+            }
+        }
+        reachable
+    }
+
+    /// Blocks that can never run, see [CFG::reachable]
+    pub fn unreachable(&self) -> Vec<usize> {
+        self.reachable()
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, reachable)| (!reachable).then_some(i))
+            .collect()
+    }
+
+    /// Outermost regions of irreducible control flow, see [IrreducibleRegion]
+    pub fn irreducible_regions(&self) -> Vec<IrreducibleRegion> {
+        IrreducibleRegion::find(self)
+    }
+
+    /// Whether every loop of the CFG is a natural loop, i.e. it has a header that dominates the rest of the loop
+    pub fn is_reducible(&self) -> bool {
+        self.irreducible_regions().is_empty()
+    }
+
+    /// Check that the CFG is reducible, for passes that only handle natural loops.
+    /// The error lists the labels of the blocks in each irreducible region.
+    pub fn check_reducible(&self) -> Result<(), Error> {
+        let regions = self.irreducible_regions();
+        if regions.is_empty() {
+            return Ok(());
+        }
+
+        let regions = regions.iter().map(|r| r.labels(self)).collect();
+        Err(Error::new(ErrorKind::Irreducible(regions)).in_func(self.name()))
+    }
+
     pub fn get(&self, idx: usize) -> &BasicBlock {
         self.func.get(idx)
     }
@@ -180,15 +362,372 @@ impl CFG {
     }
 }
 
-impl From<BBFunction> for CFG {
-    fn from(func: BBFunction) -> Self {
-        Self::new(func)
+/// Editing operations.
+/// Blocks and edges are always given in the direction of control flow, even if the CFG is reversed.
+/// New blocks are added at the end of the function, so existing blocks keep their index
+/// unless a block is deleted.
+impl CFG {
+    /// Label of a block, giving it a fresh one if it has none
+    pub fn ensure_label(&mut self, idx: usize) -> String {
+        if let Some(label) = &self.get(idx).label {
+            return label.clone();
+        }
+
+        let label = self.func.fresh_label(&format!("b{}", idx));
+        self.func.set_label(idx, label.clone());
+        label
+    }
+
+    /// Give a block an explicit jump or return if it falls through,
+    /// so that it keeps its successors when blocks are added, moved or deleted.
+    pub fn make_explicit(&mut self, idx: usize) {
+        if self.get(idx).control_flow().is_some() {
+            return;
+        }
+
+        let instr = match self.succs[idx] {
+            FlowEdge::Jump(target) => jump(self.ensure_label(target)),
+            FlowEdge::Exit => Instruction::Effect {
+                op: EffectOps::Return,
+                args: vec![],
+                funcs: vec![],
+                labels: vec![],
+                pos: None,
+            },
+            FlowEdge::Branch(..) => unreachable!("Branches are always explicit"),
+        };
+        self.get_mut(idx).push(instr);
     }
+
+    /// Redirect the edges from `from` to `old` so that they go to `new` instead
+    pub fn retarget(&mut self, from: usize, old: usize, new: usize) {
+        self.make_explicit(from);
+        let old_label = self.ensure_label(old);
+        let new_label = self.ensure_label(new);
+        self.get_mut(from).retarget(&old_label, &new_label);
+
+        let flow = self.succs[from].map(|succ| if succ == old { new } else { succ });
+        self.set_flow(from, flow);
+    }
+
+    /// Replace the control flow of a block with an unconditional jump to `target`
+    pub fn set_jump(&mut self, idx: usize, target: usize) {
+        let label = self.ensure_label(target);
+        let block = self.get_mut(idx);
+        block.take_control_flow();
+        block.push(jump(label));
+        self.set_flow(idx, FlowEdge::Jump(target));
+    }
+
+    /// Insert a block containing `instrs` on the edges from `from` to `to`, and return its index
+    pub fn insert_block_on_edge(
+        &mut self,
+        from: usize,
+        to: usize,
+        instrs: Vec<Instruction>,
+    ) -> usize {
+        let to_label = self.ensure_label(to);
+        let hint = format!("{}.{}", self.get(from).label_or_default(), to_label);
+        let label = self.func.fresh_label(&hint);
+
+        let new = self.add_block(label, instrs);
+        self.get_mut(new).push(jump(to_label));
+        self.set_flow(new, FlowEdge::Jump(to));
+        self.retarget(from, to, new);
+
+        new
+    }
+
+    /// Split a block before instruction `at`, and return the index of the block holding the rest of it.
+    /// The first half jumps to the second, which keeps the successors of the original block.
+    pub fn split_block(&mut self, idx: usize, at: usize) -> usize {
+        self.make_explicit(idx);
+        let hint = format!("{}.split", self.get(idx).label_or_default());
+        let label = self.func.fresh_label(&hint);
+
+        let instrs = self.get_mut(idx).split_off(at);
+        let new = self.add_block(label.clone(), instrs);
+        self.get_mut(idx).push(jump(label));
+
+        let flow = self.succs[idx];
+        self.set_flow(idx, FlowEdge::Jump(new));
+        self.set_flow(new, flow);
+
+        new
+    }
+
+    /// Add a copy of a block at the end of the function, with the same successors, and return its index.
+    /// The copy has no predecessors until edges are retargeted to it.
+    pub fn duplicate_block(&mut self, idx: usize) -> usize {
+        self.make_explicit(idx);
+        let hint = format!("{}.copy", self.get(idx).label_or_default());
+        let label = self.func.fresh_label(&hint);
+
+        let instrs = self.get(idx).iter().cloned().collect();
+        let new = self.add_block(label, instrs);
+        self.set_flow(new, self.succs[idx]);
+
+        new
+    }
+
+    /// Add an empty block before the first block if it has predecessors, and return whether one was added.
+    /// The new block is labeled so that it survives being converted back to a function,
+    /// and falls through to the old entry, so that the entry is never the target of a jump,
+    /// e.g. to have somewhere to place code that must run once on entry.
+    /// Every block shifts up by one index.
+    pub fn add_synthetic_entry(&mut self) -> bool {
+        if self.is_empty() || self.preds[0].is_empty() {
+            return false;
+        }
+
+        let reversed = self.reversed;
+        let label = self.func.fresh_label("entry");
+        let func = self.func.clone().with_blocks(|blocks| {
+            std::iter::once(BasicBlock::new(0, Some(label), vec![]))
+                .chain(blocks)
+                .collect()
+        });
+        *self = Self::new(func);
+        self.reversed = reversed;
+
+        true
+    }
+
+    /// Whether the edge from `from` to `to` is critical,
+    /// i.e. `from` has several successors and `to` has several predecessors
+    pub fn is_critical_edge(&self, from: usize, to: usize) -> bool {
+        self.succs[from].vec().into_iter().unique().count() > 1
+            && self.preds[to].iter().unique().count() > 1
+    }
+
+    /// Split every critical edge by inserting an empty block on it, and return the number of edges split.
+    /// Afterwards, code can be placed on any edge by adding it to the end of its source
+    /// or the start of its target.
+    pub fn split_critical_edges(&mut self) -> usize {
+        let critical = (0..self.len())
+            .flat_map(|from| self.succs[from].vec().into_iter().map(move |to| (from, to)))
+            .unique()
+            .filter(|&(from, to)| self.is_critical_edge(from, to))
+            .collect::<Vec<_>>();
+
+        // Splitting an edge does not change the number of successors or predecessors of other blocks,
+        // so the remaining edges stay critical
+        for &(from, to) in &critical {
+            self.insert_block_on_edge(from, to, vec![]);
+        }
+
+        critical.len()
+    }
+
+    /// Whether block `b` can be merged into block `a`,
+    /// i.e. `a` always continues to `b` and `b` is only reached from `a`
+    pub fn can_merge(&self, a: usize, b: usize) -> bool {
+        a != b && b != 0 && self.succs[a] == FlowEdge::Jump(b) && self.preds[b] == [a]
+    }
+
+    /// Append the instructions of block `b` to block `a` and delete `b`, see [CFG::can_merge].
+    /// Returns the new index of `a`.
+    pub fn merge_blocks(&mut self, a: usize, b: usize) -> usize {
+        assert!(
+            self.can_merge(a, b),
+            "Block {} cannot be merged into block {}",
+            b,
+            a
+        );
+
+        self.make_explicit(b);
+        self.get_mut(a).take_control_flow();
+        let instrs = self.get_mut(b).split_off(0);
+        self.get_mut(a).extend(instrs);
+
+        let flow = self.succs[b];
+        self.set_flow(b, FlowEdge::Exit);
+        self.set_flow(a, flow);
+        self.delete_block(b);
+
+        if a > b { a - 1 } else { a }
+    }
+
+    /// Make the CFG reducible by node splitting, and return the number of blocks copied.
+    /// The first entry of each irreducible region is kept as its header, while the rest of the region
+    /// is copied and the edges from outside into the other entries are moved to the copy.
+    ///
+    /// Repeating this until no irreducible regions are left terminates,
+    /// though the code size can grow exponentially in the worst case.
+    /// The moved edges come from blocks outside the strongly connected region, so the copy does not
+    /// join the region or add entries to the loops around it. The irreducible regions left in the region
+    /// and its copy are then nested inside them without the header, see [CFG::irreducible_regions],
+    /// so each split replaces a region by a bounded number of regions with fewer blocks,
+    /// which can only happen finitely many times.
+    pub fn make_reducible(&mut self) -> usize {
+        let mut copied = 0;
+
+        while let Some(region) = self.irreducible_regions().into_iter().next() {
+            let header = region.entries()[0];
+
+            let entering = region.entries()[1..]
+                .iter()
+                .flat_map(|&entry| {
+                    self.preds[entry]
+                        .iter()
+                        .filter(|pred| !region.contains(**pred))
+                        .map(move |&pred| (pred, entry))
+                })
+                .unique()
+                .collect::<Vec<_>>();
+
+            let copies = region
+                .blocks()
+                .filter(|&block| block != header)
+                .map(|block| (block, self.duplicate_block(block)))
+                .collect::<HashMap<_, _>>();
+
+            // Edges within the copy stay within it, except for those back to the header
+            for &copy in copies.values() {
+                for succ in self.succs[copy].vec().into_iter().unique() {
+                    if let Some(&succ_copy) = copies.get(&succ) {
+                        self.retarget(copy, succ, succ_copy);
+                    }
+                }
+            }
+
+            for (pred, entry) in entering {
+                self.retarget(pred, entry, copies[&entry]);
+            }
+
+            copied += copies.len();
+        }
+
+        copied
+    }
+
+    /// Delete a block that has no predecessors, and return it.
+    /// The blocks after it shift down by one index.
+    pub fn delete_block(&mut self, idx: usize) -> BasicBlock {
+        assert!(idx != 0, "Cannot delete the entry block");
+        assert!(
+            self.preds[idx].is_empty(),
+            "Cannot delete block {} as it has predecessors",
+            idx
+        );
+
+        self.set_flow(idx, FlowEdge::Exit);
+        self.preds.remove(idx);
+        self.succs.remove(idx);
+
+        let shift = |i: usize| if i > idx { i - 1 } else { i };
+        for preds in &mut self.preds {
+            for pred in preds.iter_mut() {
+                *pred = shift(*pred);
+            }
+        }
+        for flow in &mut self.succs {
+            *flow = flow.map(shift);
+        }
+
+        self.func.remove_block(idx)
+    }
+
+    /// Delete every block that can never run, see [CFG::unreachable], and return the number of blocks deleted
+    pub fn remove_unreachable_blocks(&mut self) -> usize {
+        let unreachable = self.unreachable();
+
+        // Unreachable blocks may still jump to each other, so cut their edges first
+        for &idx in &unreachable {
+            self.set_flow(idx, FlowEdge::Exit);
+        }
+
+        // Deleting from the back keeps the indices of the blocks left to delete valid
+        for &idx in unreachable.iter().rev() {
+            self.delete_block(idx);
+        }
+
+        unreachable.len()
+    }
+
+    /// Add a block with no edges at the end of the function
+    fn add_block(&mut self, label: String, instrs: Vec<Instruction>) -> usize {
+        // The last block must not fall through into the new one
+        if let Some(last) = self.len().checked_sub(1) {
+            self.make_explicit(last);
+        }
+
+        let idx = self.func.push_block(Some(label), instrs);
+        self.preds.push(vec![]);
+        self.succs.push(FlowEdge::Exit);
+        idx
+    }
+
+    /// Replace the successors of a block, keeping the predecessor lists sorted and consistent.
+    /// The control flow instruction of the block must be updated separately.
+    fn set_flow(&mut self, idx: usize, flow: FlowEdge) {
+        for succ in self.succs[idx].vec() {
+            let pos = self.preds[succ]
+                .iter()
+                .position(|&pred| pred == idx)
+                .expect("Predecessor lists are out of sync");
+            self.preds[succ].remove(pos);
+        }
+
+        for succ in flow.vec() {
+            let pos = self.preds[succ].partition_point(|&pred| pred <= idx);
+            self.preds[succ].insert(pos, idx);
+        }
+
+        self.succs[idx] = flow;
+    }
+}
+
+/// Unconditional jump to a label
+fn jump(label: String) -> Instruction {
+    Option::<Instruction>::from(ControlFlow::Jump(label)).unwrap()
 }
 
-impl From<Function> for CFG {
-    fn from(func: Function) -> Self {
-        BBFunction::new(func).into()
+impl Analysis for CFG {
+    type Result = CFG;
+
+    fn compute(_: &mut AnalysisManager, func: &BBFunction) -> Result<Self::Result, Error> {
+        CFG::try_new(func.clone())
+    }
+
+    fn refresh(cfg: &mut CFG, func: &BBFunction) -> bool {
+        // The edges only depend on the labels of the blocks and the targets of their control flow,
+        // so a pass claiming to preserve the CFG while changing them is caught here
+        // rather than leaving it out of date
+        fn targets(block: &BasicBlock) -> Option<(&EffectOps, &[String])> {
+            match block.control_flow()? {
+                Instruction::Effect { op, labels, .. } => Some((op, labels)),
+                _ => None,
+            }
+        }
+
+        let same_edges = cfg.func.len() == func.len()
+            && cfg
+                .func
+                .iter()
+                .zip(func.iter())
+                .all(|(old, new)| old.label == new.label && targets(old) == targets(new));
+
+        if same_edges {
+            cfg.func = func.clone();
+        }
+        same_edges
+    }
+}
+
+impl TryFrom<BBFunction> for CFG {
+    type Error = Error;
+
+    fn try_from(func: BBFunction) -> Result<Self, Error> {
+        Self::try_new(func)
+    }
+}
+
+impl TryFrom<Function> for CFG {
+    type Error = Error;
+
+    fn try_from(func: Function) -> Result<Self, Error> {
+        Self::try_new(BBFunction::new(func))
     }
 }
 
@@ -204,6 +743,35 @@ impl From<CFG> for Function {
     }
 }
 
+/// Edge of a CFG as it appears in JSON
+#[derive(Serialize)]
+struct JsonEdge {
+    from: usize,
+    to: usize,
+    kind: EdgeKind,
+}
+
+/// The blocks of the function and the edges between them, in the direction control flows,
+/// even if the CFG is reversed
+impl Serialize for CFG {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let edges = (0..self.len())
+            .flat_map(|from| {
+                self.succs[from]
+                    .edges()
+                    .into_iter()
+                    .map(move |(to, kind)| JsonEdge { from, to, kind })
+            })
+            .collect_vec();
+
+        let mut state = serializer.serialize_struct("CFG", 3)?;
+        state.serialize_field("name", self.name())?;
+        state.serialize_field("blocks", &self.iter().collect_vec())?;
+        state.serialize_field("edges", &edges)?;
+        state.end()
+    }
+}
+
 impl GraphLike<&BasicBlock> for CFG {
     fn node_attrs(&self, node: &BasicBlock) -> Vec<Attribute> {
         node.node_attrs()
@@ -0,0 +1,190 @@
+use super::{BBFunction, BasicBlock};
+use crate::{Analysis, AnalysisManager, CFG, EdgeKind, Error, GraphLike, PostDominatorTree};
+use bril_rs::Function;
+use graphviz_rust::{
+    dot_generator::{attr, edge, id},
+    dot_structures::{Attribute, Edge, EdgeTy, Id, Stmt, Vertex},
+};
+use serde::{Serialize, Serializer, ser::SerializeStruct};
+
+/// Control dependence graph of a function.
+/// A block is control dependent on a branch if one edge of the branch always leads to the block,
+/// but the other edge may avoid it. This is derived from post-dominance: a block is control dependent
+/// on the blocks in its post-dominance frontier.
+#[derive(Debug, Clone)]
+pub struct ControlDependenceGraph {
+    cfg: CFG,
+    /// Branches each block depends on, with the edge that leads to the block
+    deps: Vec<Vec<(usize, EdgeKind)>>,
+    /// Blocks depending on each branch, with the edge that leads to them
+    dependents: Vec<Vec<(usize, EdgeKind)>>,
+}
+
+impl ControlDependenceGraph {
+    pub fn new(tree: &PostDominatorTree) -> Self {
+        let cfg = tree.cfg();
+        let n = cfg.len();
+        let mut deps: Vec<Vec<(usize, EdgeKind)>> = vec![vec![]; n];
+
+        // For each edge from a to b where b does not post-dominate a,
+        // the blocks from b up to the immediate post-dominator of a depend on the edge.
+        // Only branches are considered, a jump from a block that cannot reach the exit
+        // may still not be post-dominated by its target because of the virtual exit edges
+        for a in 0..n {
+            if cfg.succs(a).len() < 2 {
+                continue;
+            }
+
+            for (b, kind) in cfg.succ_edges(a) {
+                let stop = tree.immediate_pdom(a);
+                let mut runner = Some(b);
+                while let Some(r) = runner
+                    && Some(r) != stop
+                    && r != tree.exit()
+                {
+                    if !deps[r].contains(&(a, kind)) {
+                        deps[r].push((a, kind));
+                    }
+                    runner = tree.immediate_pdom(r);
+                }
+            }
+        }
+
+        let mut dependents = vec![vec![]; n];
+        for (block, block_deps) in deps.iter().enumerate() {
+            for &(branch, kind) in block_deps {
+                dependents[branch].push((block, kind));
+            }
+        }
+
+        log::trace!("Control Dependences: {:?}", deps);
+
+        Self {
+            cfg: cfg.clone(),
+            deps,
+            dependents,
+        }
+    }
+
+    /// Branches a block is control dependent on, with the edge of the branch that leads to the block.
+    /// Blocks that always run once the function is entered have no dependences.
+    pub fn deps(&self, idx: usize) -> &[(usize, EdgeKind)] {
+        &self.deps[idx]
+    }
+
+    /// Blocks that are control dependent on a branch, with the edge of the branch that leads to them
+    pub fn dependents(&self, idx: usize) -> &[(usize, EdgeKind)] {
+        &self.dependents[idx]
+    }
+
+    pub fn len(&self) -> usize {
+        self.cfg.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.cfg.is_empty()
+    }
+
+    pub fn get(&self, idx: usize) -> &BasicBlock {
+        self.cfg.get(idx)
+    }
+
+    pub fn cfg(&self) -> &CFG {
+        &self.cfg
+    }
+}
+
+impl Analysis for ControlDependenceGraph {
+    type Result = ControlDependenceGraph;
+
+    fn compute(am: &mut AnalysisManager, func: &BBFunction) -> Result<Self::Result, Error> {
+        Ok(ControlDependenceGraph::new(
+            am.get::<PostDominatorTree>(func)?,
+        ))
+    }
+
+    fn refresh(cdg: &mut ControlDependenceGraph, func: &BBFunction) -> bool {
+        <CFG as Analysis>::refresh(&mut cdg.cfg, func)
+    }
+}
+
+impl From<&PostDominatorTree> for ControlDependenceGraph {
+    fn from(tree: &PostDominatorTree) -> Self {
+        Self::new(tree)
+    }
+}
+
+impl From<CFG> for ControlDependenceGraph {
+    fn from(cfg: CFG) -> Self {
+        Self::new(&PostDominatorTree::new(cfg))
+    }
+}
+
+impl TryFrom<Function> for ControlDependenceGraph {
+    type Error = Error;
+
+    fn try_from(func: Function) -> Result<Self, Error> {
+        Ok(CFG::try_from(func)?.into())
+    }
+}
+
+/// Control dependence of a block as it appears in JSON
+#[derive(Serialize)]
+struct JsonDep {
+    branch: usize,
+    kind: EdgeKind,
+}
+
+/// The CFG along with the branches each block depends on
+impl Serialize for ControlDependenceGraph {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let deps = self
+            .deps
+            .iter()
+            .map(|deps| {
+                deps.iter()
+                    .map(|&(branch, kind)| JsonDep { branch, kind })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        let mut state = serializer.serialize_struct("ControlDependenceGraph", 2)?;
+        state.serialize_field("cfg", &self.cfg)?;
+        state.serialize_field("deps", &deps)?;
+        state.end()
+    }
+}
+
+impl GraphLike<&BasicBlock> for ControlDependenceGraph {
+    fn node_attrs(&self, bb: &BasicBlock) -> Vec<Attribute> {
+        bb.node_attrs()
+    }
+
+    fn graph_attrs(&self) -> Vec<Stmt> {
+        self.cfg.graph_attrs()
+    }
+
+    fn graph_nodes(&self, gid: &[usize]) -> Vec<Stmt> {
+        self.cfg.graph_nodes(gid)
+    }
+
+    fn graph_edges(&self, gid: &[usize]) -> Vec<Stmt> {
+        self.dependents
+            .iter()
+            .enumerate()
+            .flat_map(|(branch, dependents)| {
+                dependents.iter().map(move |&(block, kind)| {
+                    let color = match kind {
+                        EdgeKind::True => "green",
+                        EdgeKind::False => "red",
+                        EdgeKind::Jump => "black",
+                    };
+                    edge!(self.node_id(gid, branch) => self.node_id(gid, block); attr!("color", color))
+                        .into()
+                })
+            })
+            .chain(super::dominator_tree::background_edges(&self.cfg, gid))
+            .collect()
+    }
+}
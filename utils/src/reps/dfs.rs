@@ -0,0 +1,150 @@
+use crate::CFG;
+
+/// Classification of an edge with respect to a depth first search
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EdgeClass {
+    /// Edge of the spanning forest, followed to first reach its target
+    Tree,
+    /// Edge to an ancestor of its source, or to the source itself
+    Back,
+    /// Edge to a proper descendant of its source that is not a tree edge
+    Forward,
+    /// Edge between blocks where neither is an ancestor of the other
+    Cross,
+}
+
+/// Depth first spanning forest of a CFG.
+/// The search starts from the entry blocks, then from any block not yet reached, so every block is included.
+/// Successors are followed in order, and a reversed CFG is searched along its reversed edges.
+#[derive(Debug, Clone)]
+pub struct DfsTree {
+    preorder: Vec<usize>,
+    postorder: Vec<usize>,
+    pre: Vec<usize>,
+    post: Vec<usize>,
+    parent: Vec<Option<usize>>,
+    edges: Vec<(usize, usize, EdgeClass)>,
+}
+
+impl DfsTree {
+    pub fn new(cfg: &CFG) -> Self {
+        let n = cfg.len();
+        let mut tree = Self {
+            preorder: Vec::with_capacity(n),
+            postorder: Vec::with_capacity(n),
+            pre: vec![usize::MAX; n],
+            post: vec![usize::MAX; n],
+            parent: vec![None; n],
+            edges: vec![],
+        };
+
+        let roots = (0..n)
+            .filter(|&i| cfg.is_entry(i))
+            .chain(0..n)
+            .collect::<Vec<_>>();
+
+        for root in roots {
+            if tree.visited(root) {
+                continue;
+            }
+
+            tree.discover(root);
+            let mut stack = vec![(root, cfg.succs(root).into_iter())];
+
+            while let Some((node, succs)) = stack.last_mut() {
+                let node = *node;
+
+                if let Some(succ) = succs.next() {
+                    let class = if !tree.visited(succ) {
+                        EdgeClass::Tree
+                    } else if tree.post[succ] == usize::MAX {
+                        // Still being searched, so it is on the stack
+                        EdgeClass::Back
+                    } else if tree.pre[succ] > tree.pre[node] {
+                        EdgeClass::Forward
+                    } else {
+                        EdgeClass::Cross
+                    };
+                    tree.edges.push((node, succ, class));
+
+                    if class == EdgeClass::Tree {
+                        tree.discover(succ);
+                        tree.parent[succ] = Some(node);
+                        stack.push((succ, cfg.succs(succ).into_iter()));
+                    }
+                } else {
+                    tree.post[node] = tree.postorder.len();
+                    tree.postorder.push(node);
+                    stack.pop();
+                }
+            }
+        }
+
+        tree
+    }
+
+    fn visited(&self, idx: usize) -> bool {
+        self.pre[idx] != usize::MAX
+    }
+
+    fn discover(&mut self, idx: usize) {
+        self.pre[idx] = self.preorder.len();
+        self.preorder.push(idx);
+    }
+
+    /// Blocks in the order they were first reached
+    pub fn preorder(&self) -> &[usize] {
+        &self.preorder
+    }
+
+    /// Blocks in the order their search finished
+    pub fn postorder(&self) -> &[usize] {
+        &self.postorder
+    }
+
+    pub fn reverse_postorder(&self) -> Vec<usize> {
+        self.postorder.iter().rev().copied().collect()
+    }
+
+    /// Position of a block in preorder
+    pub fn pre_number(&self, idx: usize) -> usize {
+        self.pre[idx]
+    }
+
+    /// Position of a block in postorder
+    pub fn post_number(&self, idx: usize) -> usize {
+        self.post[idx]
+    }
+
+    /// Parent of a block in the spanning forest, [None] for the roots
+    pub fn parent(&self, idx: usize) -> Option<usize> {
+        self.parent[idx]
+    }
+
+    /// Roots of the spanning forest, in the order they were searched
+    pub fn roots(&self) -> Vec<usize> {
+        self.preorder
+            .iter()
+            .copied()
+            .filter(|&i| self.parent[i].is_none())
+            .collect()
+    }
+
+    /// Whether `a` is an ancestor of `b` in the spanning forest, including when they are the same block
+    pub fn is_ancestor(&self, a: usize, b: usize) -> bool {
+        self.pre[a] <= self.pre[b] && self.post[b] <= self.post[a]
+    }
+
+    /// Every edge of the CFG along with its class, in the order they were explored
+    pub fn edges(&self) -> &[(usize, usize, EdgeClass)] {
+        &self.edges
+    }
+
+    /// Edges of a single class
+    pub fn edges_of(&self, class: EdgeClass) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.edges
+            .iter()
+            .filter(move |(_, _, c)| *c == class)
+            .map(|&(from, to, _)| (from, to))
+    }
+}
@@ -1,129 +1,169 @@
 use super::{BBFunction, BasicBlock};
-use crate::{CFG, Dataflow, DataflowPass, DominatorPass, GraphLike};
+use crate::{Analysis, AnalysisManager, CFG, Error, FlowEdge, GraphLike};
 use bril_rs::Function;
 use graphviz_rust::{
     dot_generator::{attr, edge, id, node_id},
     dot_structures::{Attribute, Edge, EdgeTy, Id, NodeId, Stmt, Vertex},
 };
 use linked_hash_set::LinkedHashSet;
-
+use serde::{Serialize, Serializer, ser::SerializeStruct};
+use std::cell::OnceCell;
+
+/// Dominator tree of a function, rooted at the entry block.
+/// Every block that returns or falls off the end of the function flows into a virtual exit node,
+/// with index `cfg.len()`.
+/// Blocks that cannot be reached from the entry, see [CFG::unreachable], have no immediate dominator
+/// and are only dominated by themselves, so facts about them never leak into the rest of the tree.
 #[derive(Debug, Clone)]
 pub struct DominatorTree {
     /// The control flow graph.
     cfg: CFG,
-    /// The strict dominator set for each basic block.
-    strict_doms: Vec<LinkedHashSet<usize>>,
-    /// Immediate dominator for each basic block.
+    /// Immediate dominator for each basic block and the exit.
     immediate_doms: Vec<Option<usize>>,
-    /// Dominance frontier for each basic block.
-    dominance_frontiers: Vec<LinkedHashSet<usize>>,
+    /// Children of each node in the tree.
+    children: Vec<Vec<usize>>,
+    /// Preorder number of each node in the tree.
+    pre: Vec<usize>,
+    /// Postorder number of each node in the tree.
+    post: Vec<usize>,
+    /// Dominance frontier for each basic block and the exit, computed on first use.
+    dominance_frontiers: OnceCell<Vec<LinkedHashSet<usize>>>,
 }
 
 impl DominatorTree {
     pub fn new(cfg: CFG) -> Self {
-        let Dataflow {
-            out_vals: mut doms,
-            cfg,
-            exit_val: exit_doms,
-            ..
-        } = DominatorPass.cfg(cfg);
-
+        let cfg = if cfg.reversed() { cfg.reverse() } else { cfg };
         let n = cfg.len();
 
-        // Add the exit doms to the set
-        doms.push(exit_doms);
-
-        // A block's dominance frontier is a set of values it does not dominate
-        // but it dominates a predecessor of the value.
-        // First, find the set of nodes dominated by each block.
-        let mut dom_bys = vec![LinkedHashSet::new(); n + 1];
-        for (i, doms) in doms.iter().enumerate() {
-            for &dom in doms {
-                // dom dominates i
-                dom_bys[dom].insert(i);
-            }
-        }
-
-        // Now, find the dominance frontier for each block.
-        let dominance_frontiers = dom_bys
-            .iter()
-            .map(|dom_by| {
-                // Get the set of all successors of dominated blocks
-                let candidates: LinkedHashSet<_> = dom_by
-                    .iter()
-                    .flat_map(|&dom| if dom < n { cfg.succs(dom) } else { vec![] })
-                    .collect();
-
-                // The dominance frontier is the set of all successors that are not strictly dominated by i
-                candidates.difference(dom_by).copied().collect()
-            })
-            .collect();
+        let immediate_doms = if n == 0 {
+            vec![None]
+        } else {
+            immediate_dominators(
+                n + 1,
+                0,
+                |idx| exit_preds(&cfg, idx),
+                |idx| exit_succs(&cfg, idx),
+            )
+        };
 
-        let mut strict_doms = doms;
-        log::trace!("Dominators: {:?}", strict_doms);
+        log::trace!("Immediate Dominators: {:?}", immediate_doms);
 
-        // Make dominator sets strict
-        for (i, sdom) in strict_doms.iter_mut().enumerate() {
-            sdom.remove(&i);
+        let mut children = vec![vec![]; n + 1];
+        for (node, &idom) in immediate_doms.iter().enumerate() {
+            if let Some(idom) = idom {
+                children[idom].push(node);
+            }
         }
 
-        log::trace!("Strict Dominators: {:?}", strict_doms);
-
-        // A dominator is immediate if it is in the dominator set of the block but not in the dominator set of any other dominator.
-
-        let immediate_doms = strict_doms
-            .iter()
-            .map(|sdoms| {
-                // These are dominators that dominate other dominators, so cannot be immediate dominators.
-                let non_immediate_doms: LinkedHashSet<_> = sdoms
-                    .iter()
-                    .flat_map(|&d| &strict_doms[d])
-                    .copied()
-                    .collect();
-
-                let candidates: Vec<_> = sdoms.difference(&non_immediate_doms).collect();
-
-                // There should be at most one immediate dominator.
-                assert!(candidates.len() <= 1, "Multiple immediate dominators found");
-
-                candidates.into_iter().next().copied()
-            })
-            .collect();
-
-        log::trace!("Immediate Dominators: {:?}", immediate_doms);
-
-        log::trace!("Dominance Frontiers: {:?}", dominance_frontiers);
+        // Number the nodes of the tree, so that a node dominates another
+        // if the other is visited while the node is on the stack
+        let mut pre = vec![0; n + 1];
+        let mut post = vec![0; n + 1];
+        let (mut pre_count, mut post_count) = (0, 0);
+        // Unreachable blocks are roots of their own trees, each tree is numbered in turn
+        // so that their intervals never overlap
+        for root in (0..=n).filter(|&node| immediate_doms[node].is_none()) {
+            pre[root] = pre_count;
+            pre_count += 1;
+            let mut stack = vec![(root, 0)];
+            while let Some((node, child)) = stack.last_mut() {
+                let node = *node;
+                if let Some(&next) = children[node].get(*child) {
+                    *child += 1;
+                    pre[next] = pre_count;
+                    pre_count += 1;
+                    stack.push((next, 0));
+                } else {
+                    post[node] = post_count;
+                    post_count += 1;
+                    stack.pop();
+                }
+            }
+        }
 
         Self {
             cfg,
-            strict_doms,
             immediate_doms,
-            dominance_frontiers,
+            children,
+            pre,
+            post,
+            dominance_frontiers: OnceCell::new(),
         }
     }
 
-    pub fn strict_doms(&self, idx: usize) -> &LinkedHashSet<usize> {
-        &self.strict_doms[idx]
+    /// Index of the virtual exit node
+    pub fn exit(&self) -> usize {
+        self.cfg.len()
     }
 
+    /// Blocks strictly dominating a block, from its immediate dominator up to the entry
+    pub fn strict_doms(&self, idx: usize) -> LinkedHashSet<usize> {
+        std::iter::successors(self.immediate_doms[idx], |&i| self.immediate_doms[i]).collect()
+    }
+
+    /// Blocks dominating a block, from the block itself up to the entry
     pub fn dominators(&self, idx: usize) -> LinkedHashSet<usize> {
-        self.strict_doms[idx]
-            .iter()
-            .copied()
-            .chain(std::iter::once(idx))
-            .collect()
+        std::iter::successors(Some(idx), |&i| self.immediate_doms[i]).collect()
     }
 
     pub fn immediate_doms(&self, idx: usize) -> Option<usize> {
         self.immediate_doms[idx]
     }
 
+    /// Nodes immediately dominated by a node
+    pub fn children(&self, idx: usize) -> &[usize] {
+        &self.children[idx]
+    }
+
+    /// Blocks where the dominance of a block ends.
+    /// A loop header is in its own frontier, since it dominates its latches but does not strictly
+    /// dominate itself, so variables assigned in a loop get a phi at its header.
     pub fn dominance_frontier(&self, idx: usize) -> &LinkedHashSet<usize> {
-        &self.dominance_frontiers[idx]
+        &self
+            .dominance_frontiers
+            .get_or_init(|| self.dominance_frontiers())[idx]
+    }
+
+    /// A block's dominance frontier is the set of blocks it does not strictly dominate,
+    /// but which have a predecessor it dominates.
+    /// Join points are in the frontier of the nodes on the paths from each of their
+    /// predecessors up to their immediate dominator, or up to the root for the entry,
+    /// which has none but may still be jumped back to.
+    /// Predecessors that cannot be reached are skipped, since they are not dominated by anything.
+    fn dominance_frontiers(&self) -> Vec<LinkedHashSet<usize>> {
+        let n = self.cfg.len();
+        let reachable = self.cfg.reachable();
+        let mut frontiers = vec![LinkedHashSet::new(); n + 1];
+
+        for node in 0..=n {
+            // A single predecessor is the immediate dominator of the node, so it only needs
+            // to be walked for nodes without one
+            let preds = exit_preds(&self.cfg, node)
+                .into_iter()
+                .filter(|&pred| reachable[pred])
+                .collect::<Vec<_>>();
+            if preds.len() < 2 && self.immediate_doms[node].is_some() {
+                continue;
+            }
+
+            for pred in preds {
+                let mut runner = Some(pred);
+                while let Some(r) = runner
+                    && Some(r) != self.immediate_doms[node]
+                {
+                    frontiers[r].insert(node);
+                    runner = self.immediate_doms[r];
+                }
+            }
+        }
+
+        log::trace!("Dominance Frontiers: {:?}", frontiers);
+
+        frontiers
     }
 
     pub fn strictly_dominates(&self, a: usize, b: usize) -> bool {
-        self.strict_doms[b].contains(&a)
+        a != b && self.dominates(a, b)
     }
 
     pub fn strictly_dominated_by(&self, a: usize, b: usize) -> bool {
@@ -131,7 +171,7 @@ impl DominatorTree {
     }
 
     pub fn dominates(&self, a: usize, b: usize) -> bool {
-        a == b || self.strictly_dominates(a, b)
+        self.pre[a] <= self.pre[b] && self.post[b] <= self.post[a]
     }
 
     pub fn dominated_by(&self, a: usize, b: usize) -> bool {
@@ -168,21 +208,120 @@ impl DominatorTree {
     }
 }
 
+/// Immediate dominators of a graph with `n` nodes using the iterative algorithm of Cooper, Harvey and Kennedy.
+/// The root and the nodes unreachable from it have no immediate dominator.
+pub(crate) fn immediate_dominators(
+    n: usize,
+    root: usize,
+    preds: impl Fn(usize) -> Vec<usize>,
+    succs: impl Fn(usize) -> Vec<usize>,
+) -> Vec<Option<usize>> {
+    // Reverse postorder from the root
+    let mut postorder = Vec::with_capacity(n);
+    let mut visited = vec![false; n];
+    visited[root] = true;
+    let mut stack = vec![(root, succs(root).into_iter())];
+    while let Some((node, node_succs)) = stack.last_mut() {
+        if let Some(succ) = node_succs.find(|&succ| !visited[succ]) {
+            visited[succ] = true;
+            stack.push((succ, succs(succ).into_iter()));
+        } else {
+            postorder.push(*node);
+            stack.pop();
+        }
+    }
+
+    let mut rank = vec![usize::MAX; n];
+    for (i, &node) in postorder.iter().rev().enumerate() {
+        rank[node] = i;
+    }
+
+    let mut idoms = vec![None; n];
+    idoms[root] = Some(root);
+
+    let intersect = |idoms: &[Option<usize>], mut a: usize, mut b: usize| {
+        while a != b {
+            while rank[a] > rank[b] {
+                a = idoms[a].unwrap();
+            }
+            while rank[b] > rank[a] {
+                b = idoms[b].unwrap();
+            }
+        }
+        a
+    };
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+
+        for &node in postorder.iter().rev().filter(|&&node| node != root) {
+            let new_idom = preds(node)
+                .into_iter()
+                .filter(|&pred| idoms[pred].is_some())
+                .reduce(|a, b| intersect(&idoms, a, b));
+
+            if new_idom.is_some() && idoms[node] != new_idom {
+                idoms[node] = new_idom;
+                changed = true;
+            }
+        }
+    }
+
+    idoms[root] = None;
+    idoms
+}
+
+/// Predecessors of a block, or of the exit node at index `cfg.len()`
+pub(crate) fn exit_preds(cfg: &CFG, idx: usize) -> Vec<usize> {
+    match idx {
+        _ if idx == cfg.len() => cfg.exits(),
+        _ => cfg.preds(idx),
+    }
+}
+
+/// Successors of a block, including the exit node at index `cfg.len()`
+pub(crate) fn exit_succs(cfg: &CFG, idx: usize) -> Vec<usize> {
+    match idx {
+        _ if idx == cfg.len() => vec![],
+        _ => match cfg.flow_edge(idx) {
+            FlowEdge::Exit => vec![cfg.len()],
+            flow => flow.vec(),
+        },
+    }
+}
+
+impl Analysis for DominatorTree {
+    type Result = DominatorTree;
+
+    fn compute(am: &mut AnalysisManager, func: &BBFunction) -> Result<Self::Result, Error> {
+        Ok(DominatorTree::new(am.get::<CFG>(func)?.clone()))
+    }
+
+    fn refresh(tree: &mut DominatorTree, func: &BBFunction) -> bool {
+        <CFG as Analysis>::refresh(&mut tree.cfg, func)
+    }
+}
+
 impl From<CFG> for DominatorTree {
     fn from(cfg: CFG) -> Self {
         Self::new(cfg)
     }
 }
 
-impl From<BBFunction> for DominatorTree {
-    fn from(func: BBFunction) -> Self {
-        CFG::from(func).into()
+impl TryFrom<BBFunction> for DominatorTree {
+    type Error = Error;
+
+    fn try_from(func: BBFunction) -> Result<Self, Error> {
+        Ok(CFG::try_from(func)?.into())
     }
 }
 
-impl From<Function> for DominatorTree {
-    fn from(func: Function) -> Self {
-        CFG::from(func).into()
+impl TryFrom<Function> for DominatorTree {
+    type Error = Error;
+
+    fn try_from(func: Function) -> Result<Self, Error> {
+        Ok(CFG::try_from(func)?.into())
     }
 }
 
@@ -198,6 +337,32 @@ impl From<DominatorTree> for Function {
     }
 }
 
+/// The CFG along with the immediate dominator and dominance frontier of each block,
+/// leaving out the virtual exit node
+impl Serialize for DominatorTree {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let n = self.len();
+        let frontiers = (0..n)
+            .map(|idx| {
+                let mut frontier = self
+                    .dominance_frontier(idx)
+                    .iter()
+                    .copied()
+                    .filter(|&b| b != self.exit())
+                    .collect::<Vec<_>>();
+                frontier.sort_unstable();
+                frontier
+            })
+            .collect::<Vec<_>>();
+
+        let mut state = serializer.serialize_struct("DominatorTree", 3)?;
+        state.serialize_field("cfg", &self.cfg)?;
+        state.serialize_field("idom", &self.immediate_doms[..n])?;
+        state.serialize_field("frontier", &frontiers)?;
+        state.end()
+    }
+}
+
 impl GraphLike<&BasicBlock> for DominatorTree {
     fn node_attrs(&self, bb: &BasicBlock) -> Vec<Attribute> {
         bb.node_attrs()
@@ -228,44 +393,50 @@ impl GraphLike<&BasicBlock> for DominatorTree {
                 })
             })
             .map(|(src, dst)| edge!(src => dst; attr!("color", "black")).into())
-            .chain(self.cfg.graph_edges(gid).into_iter().map(|e| {
-                match e {
-                    Stmt::Edge(mut e) => {
-                        // Replace the color of the edge
-                        if let Some(Attribute(_, Id::Plain(v))) =
-                            e.attributes.iter_mut().find(|attr| {
-                                if let Attribute(Id::Plain(k), _) = attr {
-                                    k == "color"
-                                } else {
-                                    false
-                                }
-                            })
-                        {
-                            *v = match v.as_str() {
-                                "black" => "gray",
-                                "red" => "firebrick",
-                                "green" => "forestgreen",
-                                _ => panic!("Unexpected color: {}", v),
-                            }
-                            .to_owned()
+            .chain(background_edges(&self.cfg, gid))
+            .collect()
+    }
+}
+
+/// Edges of a CFG drawn in the background of another graph over its blocks
+pub(crate) fn background_edges(cfg: &CFG, gid: &[usize]) -> Vec<Stmt> {
+    cfg.graph_edges(gid)
+        .into_iter()
+        .map(|e| {
+            match e {
+                Stmt::Edge(mut e) => {
+                    // Replace the color of the edge
+                    if let Some(Attribute(_, Id::Plain(v))) = e.attributes.iter_mut().find(|attr| {
+                        if let Attribute(Id::Plain(k), _) = attr {
+                            k == "color"
                         } else {
-                            // Set color to gray
-                            e.attributes.push(attr!("color", "gray"));
+                            false
+                        }
+                    }) {
+                        *v = match v.as_str() {
+                            "black" => "gray",
+                            "red" => "firebrick",
+                            "green" => "forestgreen",
+                            _ => panic!("Unexpected color: {}", v),
                         }
+                        .to_owned()
+                    } else {
+                        // Set color to gray
+                        e.attributes.push(attr!("color", "gray"));
+                    }
 
-                        // add new attributes
-                        e.attributes.extend(vec![
-                            attr!("style", "dashed"),
-                            attr!("constraint", "false"),
-                            attr!("penwidth", 0.75),
-                            attr!("arrowsize", 0.75),
-                        ]);
+                    // add new attributes
+                    e.attributes.extend(vec![
+                        attr!("style", "dashed"),
+                        attr!("constraint", "false"),
+                        attr!("penwidth", 0.75),
+                        attr!("arrowsize", 0.75),
+                    ]);
 
-                        e.into()
-                    }
-                    _ => unreachable!(),
+                    e.into()
                 }
-            }))
-            .collect()
-    }
+                _ => unreachable!(),
+            }
+        })
+        .collect()
 }
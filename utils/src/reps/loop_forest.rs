@@ -0,0 +1,236 @@
+use crate::{Analysis, AnalysisManager, BBFunction, DominatorTree, Error};
+use itertools::Itertools;
+use std::collections::BTreeSet;
+
+/// A natural loop, i.e. a header and the blocks that can reach one of its back edges without going through it.
+/// Back edges to the same header are merged into a single loop.
+#[derive(Debug, Clone)]
+pub struct Loop {
+    idx: usize,
+    header: usize,
+    body: BTreeSet<usize>,
+    latches: Vec<usize>,
+    exits: Vec<(usize, usize)>,
+    preheader: Option<usize>,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    depth: usize,
+}
+
+impl Loop {
+    /// Index of the loop in its [LoopForest]
+    pub fn idx(&self) -> usize {
+        self.idx
+    }
+
+    /// The block every path into the loop goes through
+    pub fn header(&self) -> usize {
+        self.header
+    }
+
+    /// Blocks of the loop in increasing order, including the header and the blocks of nested loops
+    pub fn body(&self) -> impl Iterator<Item = usize> + '_ {
+        self.body.iter().copied()
+    }
+
+    pub fn contains(&self, block: usize) -> bool {
+        self.body.contains(&block)
+    }
+
+    pub fn len(&self) -> usize {
+        self.body.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.body.is_empty()
+    }
+
+    /// Blocks with a back edge to the header
+    pub fn latches(&self) -> &[usize] {
+        &self.latches
+    }
+
+    /// Edges leaving the loop, from a block inside it to a block outside it
+    pub fn exits(&self) -> &[(usize, usize)] {
+        &self.exits
+    }
+
+    /// Blocks outside the loop that are the target of an exit edge
+    pub fn exit_blocks(&self) -> Vec<usize> {
+        self.exits.iter().map(|&(_, to)| to).unique().collect()
+    }
+
+    /// The only block outside the loop that enters it, if it has no other successors
+    pub fn preheader(&self) -> Option<usize> {
+        self.preheader
+    }
+
+    /// Index of the innermost loop containing this one
+    pub fn parent(&self) -> Option<usize> {
+        self.parent
+    }
+
+    /// Indices of the loops directly nested in this one
+    pub fn children(&self) -> &[usize] {
+        &self.children
+    }
+
+    /// Number of loops this one is nested in, including itself
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+}
+
+/// Loop nesting forest of a function, built from the back edges of its dominator tree.
+/// Loops are ordered from innermost to outermost, so each loop comes before the loops containing it.
+/// Loops that are not natural, i.e. irreducible control flow, are not found.
+#[derive(Debug, Clone)]
+pub struct LoopForest {
+    loops: Vec<Loop>,
+    /// Innermost loop containing each block
+    innermost: Vec<Option<usize>>,
+}
+
+impl LoopForest {
+    pub fn new(tree: &DominatorTree) -> Self {
+        let cfg = tree.cfg();
+        let n = cfg.len();
+        let reachable = cfg.reachable();
+
+        // An edge is a back edge if its target dominates its source
+        let mut latches: Vec<Vec<usize>> = vec![vec![]; n];
+        for tail in (0..n).filter(|&i| reachable[i]) {
+            for head in cfg.succs(tail).into_iter().unique() {
+                if tree.dominates(head, tail) {
+                    latches[head].push(tail);
+                }
+            }
+        }
+
+        let mut loops = latches
+            .into_iter()
+            .enumerate()
+            .filter(|(_, latches)| !latches.is_empty())
+            .map(|(header, latches)| {
+                // Walk backwards from the latches, stopping at the header
+                let mut body = BTreeSet::from([header]);
+                let mut stack = latches.clone();
+                while let Some(block) = stack.pop() {
+                    if reachable[block] && body.insert(block) {
+                        stack.extend(cfg.preds(block));
+                    }
+                }
+
+                let exits = body
+                    .iter()
+                    .flat_map(|&from| cfg.succs(from).into_iter().map(move |to| (from, to)))
+                    .filter(|(_, to)| !body.contains(to))
+                    .unique()
+                    .collect();
+
+                let entering = cfg
+                    .preds(header)
+                    .into_iter()
+                    .filter(|pred| !body.contains(pred))
+                    .unique()
+                    .collect_vec();
+                let preheader = match entering[..] {
+                    [pred] if cfg.succs(pred).iter().all(|&succ| succ == header) => Some(pred),
+                    _ => None,
+                };
+
+                Loop {
+                    idx: 0,
+                    header,
+                    body,
+                    latches,
+                    exits,
+                    preheader,
+                    parent: None,
+                    children: vec![],
+                    depth: 0,
+                }
+            })
+            .collect_vec();
+
+        // Nested loops are strictly smaller than the loops containing them
+        loops.sort_by_key(|l| (l.body.len(), l.header));
+
+        for i in 0..loops.len() {
+            loops[i].idx = i;
+            let header = loops[i].header;
+            loops[i].parent = (i + 1..loops.len()).find(|&j| loops[j].contains(header));
+
+            if let Some(parent) = loops[i].parent {
+                loops[parent].children.push(i);
+            }
+        }
+
+        // Parents come after their children, so go from outermost to innermost
+        for i in (0..loops.len()).rev() {
+            loops[i].depth = loops[i].parent.map_or(0, |parent| loops[parent].depth) + 1;
+        }
+
+        let mut innermost = vec![None; n];
+        for l in &loops {
+            for block in l.body() {
+                innermost[block].get_or_insert(l.idx);
+            }
+        }
+
+        Self { loops, innermost }
+    }
+
+    pub fn len(&self) -> usize {
+        self.loops.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.loops.is_empty()
+    }
+
+    pub fn get(&self, idx: usize) -> &Loop {
+        &self.loops[idx]
+    }
+
+    /// Loops from innermost to outermost
+    pub fn iter(&self) -> impl Iterator<Item = &Loop> {
+        self.loops.iter()
+    }
+
+    /// Loops that are not nested in any other loop
+    pub fn top_level(&self) -> impl Iterator<Item = &Loop> {
+        self.loops.iter().filter(|l| l.parent.is_none())
+    }
+
+    /// Innermost loop containing a block
+    pub fn innermost(&self, block: usize) -> Option<&Loop> {
+        self.innermost[block].map(|idx| &self.loops[idx])
+    }
+
+    /// Loop with the given header
+    pub fn with_header(&self, header: usize) -> Option<&Loop> {
+        self.innermost(header).filter(|l| l.header == header)
+    }
+
+    /// Number of loops a block is nested in
+    pub fn depth(&self, block: usize) -> usize {
+        self.innermost(block).map_or(0, Loop::depth)
+    }
+}
+
+impl Analysis for LoopForest {
+    type Result = LoopForest;
+
+    fn compute(am: &mut AnalysisManager, func: &BBFunction) -> Result<Self::Result, Error> {
+        Ok(LoopForest::new(am.get::<DominatorTree>(func)?))
+    }
+}
+
+impl From<&DominatorTree> for LoopForest {
+    fn from(tree: &DominatorTree) -> Self {
+        Self::new(tree)
+    }
+}
@@ -1,11 +1,23 @@
 mod basic_block;
 mod bb_function;
+mod bit_set;
 mod call_graph;
 mod cfg;
+mod control_dependence;
+mod dfs;
 mod dominator_tree;
+mod loop_forest;
+mod post_dominator_tree;
+mod reducibility;
 
 pub use basic_block::{BasicBlock, ControlFlow};
 pub use bb_function::BBFunction;
+pub use bit_set::BitSet;
 pub use call_graph::CallGraph;
-pub use cfg::CFG;
+pub use cfg::{CFG, EdgeKind, FlowEdge};
+pub use control_dependence::ControlDependenceGraph;
+pub use dfs::{DfsTree, EdgeClass};
 pub use dominator_tree::DominatorTree;
+pub use loop_forest::{Loop, LoopForest};
+pub use post_dominator_tree::PostDominatorTree;
+pub use reducibility::IrreducibleRegion;
@@ -0,0 +1,269 @@
+use super::dominator_tree::{background_edges, exit_preds, exit_succs, immediate_dominators};
+use super::{BBFunction, BasicBlock};
+use crate::{Analysis, AnalysisManager, CFG, Error, GraphLike};
+use bril_rs::Function;
+use graphviz_rust::{
+    dot_generator::{attr, edge, id, node_id},
+    dot_structures::{Attribute, Edge, EdgeTy, Id, NodeId, Stmt, Vertex},
+};
+use linked_hash_set::LinkedHashSet;
+use serde::{Serialize, Serializer, ser::SerializeStruct};
+
+/// Post-dominator tree of a function.
+/// Every block that returns or falls off the end of the function flows into a virtual exit node,
+/// with index `cfg.len()`, which is the root of the tree.
+/// Blocks that never reach the exit, e.g. infinite loops, are given a virtual edge to the exit
+/// from the last block of a depth-first search of their region.
+#[derive(Debug, Clone)]
+pub struct PostDominatorTree {
+    /// The control flow graph, never reversed.
+    cfg: CFG,
+    /// Immediate post-dominator for each block and the exit.
+    immediate_pdoms: Vec<Option<usize>>,
+    /// Post-dominance frontier for each block and the exit.
+    frontiers: Vec<LinkedHashSet<usize>>,
+}
+
+impl PostDominatorTree {
+    pub fn new(cfg: CFG) -> Self {
+        let cfg = if cfg.reversed() { cfg.reverse() } else { cfg };
+        let n = cfg.len();
+        let exit = n;
+
+        let virtual_exits = virtual_exits(&cfg);
+        log::trace!("Virtual exits: {:?}", virtual_exits);
+
+        let succs = |idx: usize| {
+            let mut succs = exit_succs(&cfg, idx);
+            if virtual_exits.contains(&idx) {
+                succs.push(exit);
+            }
+            succs
+        };
+        let preds = |idx: usize| {
+            let mut preds = exit_preds(&cfg, idx);
+            if idx == exit {
+                preds.extend(&virtual_exits);
+            }
+            preds
+        };
+
+        // Post-dominators are the dominators of the reversed CFG, rooted at the exit,
+        // so its predecessors are the successors in the CFG and vice versa.
+        // With the virtual edges every block reaches the exit, so every block has one
+        let immediate_pdoms = immediate_dominators(n + 1, exit, succs, preds);
+
+        // Blocks with several successors are in the post-dominance frontier of the blocks
+        // on the paths from each successor up to the block's immediate post-dominator
+        let mut frontiers = vec![LinkedHashSet::new(); n + 1];
+        // Only the real edges are branches, the virtual ones never run
+        for block in 0..n {
+            let block_succs = exit_succs(&cfg, block);
+            if block_succs.len() < 2 {
+                continue;
+            }
+
+            for succ in block_succs {
+                let mut runner = Some(succ);
+                while let Some(r) = runner
+                    && Some(r) != immediate_pdoms[block]
+                {
+                    frontiers[r].insert(block);
+                    runner = immediate_pdoms[r];
+                }
+            }
+        }
+
+        log::trace!("Immediate Post-Dominators: {:?}", immediate_pdoms);
+        log::trace!("Post-Dominance Frontiers: {:?}", frontiers);
+
+        Self {
+            cfg,
+            immediate_pdoms,
+            frontiers,
+        }
+    }
+
+    /// Index of the virtual exit node
+    pub fn exit(&self) -> usize {
+        self.cfg.len()
+    }
+
+    pub fn immediate_pdom(&self, idx: usize) -> Option<usize> {
+        self.immediate_pdoms[idx]
+    }
+
+    /// Blocks post-dominating a block, from the block itself up to the exit
+    pub fn post_dominators(&self, idx: usize) -> LinkedHashSet<usize> {
+        std::iter::successors(Some(idx), |&i| self.immediate_pdoms[i]).collect()
+    }
+
+    pub fn post_dominance_frontier(&self, idx: usize) -> &LinkedHashSet<usize> {
+        &self.frontiers[idx]
+    }
+
+    pub fn post_dominates(&self, a: usize, b: usize) -> bool {
+        std::iter::successors(Some(b), |&i| self.immediate_pdoms[i]).any(|i| i == a)
+    }
+
+    pub fn strictly_post_dominates(&self, a: usize, b: usize) -> bool {
+        a != b && self.post_dominates(a, b)
+    }
+
+    pub fn len(&self) -> usize {
+        self.cfg.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.cfg.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &BasicBlock> {
+        self.cfg.iter()
+    }
+
+    pub fn get(&self, idx: usize) -> &BasicBlock {
+        self.cfg.get(idx)
+    }
+
+    pub fn cfg(&self) -> &CFG {
+        &self.cfg
+    }
+}
+
+/// Blocks given a virtual edge to the exit so that every block reaches it.
+/// For each region that cannot reach the exit, a depth-first search is run from its first block,
+/// and the last block visited, e.g. the latch of an infinite loop, gets the edge.
+/// This keeps the post-dominance between the blocks of the region, which would otherwise
+/// all be immediately post-dominated by the exit.
+fn virtual_exits(cfg: &CFG) -> Vec<usize> {
+    let n = cfg.len();
+    let mut reaches_exit = vec![false; n];
+    let mark = |reaches_exit: &mut Vec<bool>, mut stack: Vec<usize>| {
+        while let Some(block) = stack.pop() {
+            if !reaches_exit[block] {
+                reaches_exit[block] = true;
+                stack.extend(cfg.preds(block));
+            }
+        }
+    };
+    mark(&mut reaches_exit, cfg.exits());
+
+    let mut exits = vec![];
+    let mut visited = vec![false; n];
+    for start in 0..n {
+        if reaches_exit[start] {
+            continue;
+        }
+
+        let mut latest = start;
+        let mut stack = vec![start];
+        while let Some(block) = stack.pop() {
+            if visited[block] || reaches_exit[block] {
+                continue;
+            }
+            visited[block] = true;
+            latest = block;
+            stack.extend(cfg.succs(block).into_iter().rev());
+        }
+
+        exits.push(latest);
+        mark(&mut reaches_exit, vec![latest]);
+    }
+
+    exits
+}
+
+impl Analysis for PostDominatorTree {
+    type Result = PostDominatorTree;
+
+    fn compute(am: &mut AnalysisManager, func: &BBFunction) -> Result<Self::Result, Error> {
+        Ok(PostDominatorTree::new(am.get::<CFG>(func)?.clone()))
+    }
+
+    fn refresh(tree: &mut PostDominatorTree, func: &BBFunction) -> bool {
+        <CFG as Analysis>::refresh(&mut tree.cfg, func)
+    }
+}
+
+impl From<CFG> for PostDominatorTree {
+    fn from(cfg: CFG) -> Self {
+        Self::new(cfg)
+    }
+}
+
+impl TryFrom<BBFunction> for PostDominatorTree {
+    type Error = Error;
+
+    fn try_from(func: BBFunction) -> Result<Self, Error> {
+        Ok(CFG::try_from(func)?.into())
+    }
+}
+
+impl TryFrom<Function> for PostDominatorTree {
+    type Error = Error;
+
+    fn try_from(func: Function) -> Result<Self, Error> {
+        Ok(CFG::try_from(func)?.into())
+    }
+}
+
+/// The CFG along with the immediate post-dominator and post-dominance frontier of each block.
+/// Blocks that exit the function are immediately post-dominated by the virtual exit node, which is [None] here.
+impl Serialize for PostDominatorTree {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let n = self.len();
+        let ipdoms = self.immediate_pdoms[..n]
+            .iter()
+            .map(|ipdom| ipdom.filter(|&b| b != self.exit()))
+            .collect::<Vec<_>>();
+        let frontiers = self.frontiers[..n]
+            .iter()
+            .map(|frontier| {
+                let mut frontier = frontier.iter().copied().collect::<Vec<_>>();
+                frontier.sort_unstable();
+                frontier
+            })
+            .collect::<Vec<_>>();
+
+        let mut state = serializer.serialize_struct("PostDominatorTree", 3)?;
+        state.serialize_field("cfg", &self.cfg)?;
+        state.serialize_field("ipdom", &ipdoms)?;
+        state.serialize_field("frontier", &frontiers)?;
+        state.end()
+    }
+}
+
+impl GraphLike<&BasicBlock> for PostDominatorTree {
+    fn node_attrs(&self, bb: &BasicBlock) -> Vec<Attribute> {
+        bb.node_attrs()
+    }
+
+    fn graph_attrs(&self) -> Vec<Stmt> {
+        self.cfg.graph_attrs()
+    }
+
+    fn graph_nodes(&self, gid: &[usize]) -> Vec<Stmt> {
+        self.cfg.graph_nodes(gid)
+    }
+
+    fn graph_edges(&self, gid: &[usize]) -> Vec<Stmt> {
+        let exit_node = &format!("{}_exit", self.graph_id(gid));
+        let node_id = |idx: usize| {
+            if idx == self.exit() {
+                node_id!(exit_node)
+            } else {
+                self.node_id(gid, idx)
+            }
+        };
+
+        self.immediate_pdoms
+            .iter()
+            .enumerate()
+            .filter_map(|(node, &pdom)| pdom.map(|pdom| (node_id(pdom), node_id(node))))
+            .map(|(src, dst)| edge!(src => dst; attr!("color", "black")).into())
+            .chain(background_edges(&self.cfg, gid))
+            .collect()
+    }
+}
@@ -0,0 +1,149 @@
+use crate::CFG;
+use std::collections::BTreeSet;
+
+/// Strongly connected region of a CFG that can be entered at more than one block,
+/// so it is a loop with no single header that dominates the rest of it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IrreducibleRegion {
+    blocks: BTreeSet<usize>,
+    entries: Vec<usize>,
+}
+
+impl IrreducibleRegion {
+    /// Find the outermost irreducible regions of a CFG, ignoring blocks unreachable from the entry.
+    /// The forward edges are used even if the CFG is reversed.
+    ///
+    /// Each strongly connected region with a single entry is a natural loop,
+    /// so it is searched again without its header to find the irreducible regions nested inside it.
+    pub(crate) fn find(cfg: &CFG) -> Vec<Self> {
+        let n = cfg.len();
+        let succs = (0..n).map(|i| cfg.flow_edge(i).vec()).collect::<Vec<_>>();
+        let mut preds = vec![vec![]; n];
+        for (from, tos) in succs.iter().enumerate() {
+            for &to in tos {
+                preds[to].push(from);
+            }
+        }
+
+        let reachable = cfg.reachable();
+        let mut regions = vec![];
+        let mut stack = vec![(0..n).filter(|&i| reachable[i]).collect::<BTreeSet<_>>()];
+
+        while let Some(nodes) = stack.pop() {
+            for scc in components(&nodes, &succs, &preds) {
+                let is_cycle = scc.len() > 1 || succs[scc[0]].contains(&scc[0]);
+                if !is_cycle {
+                    continue;
+                }
+
+                let blocks = scc.into_iter().collect::<BTreeSet<_>>();
+                let entries = blocks
+                    .iter()
+                    .copied()
+                    .filter(|&b| {
+                        b == 0
+                            || preds[b]
+                                .iter()
+                                .any(|p| reachable[*p] && !blocks.contains(p))
+                    })
+                    .collect::<Vec<_>>();
+
+                match entries[..] {
+                    [header] => {
+                        let mut body = blocks;
+                        body.remove(&header);
+                        stack.push(body);
+                    }
+                    _ => regions.push(Self { blocks, entries }),
+                }
+            }
+        }
+
+        // Order the entries so that the first one is reached first, which is the one kept as the header
+        // when the region is split
+        let dfs = cfg.dfs();
+        for region in &mut regions {
+            region.entries.sort_by_key(|&b| dfs.pre_number(b));
+        }
+        regions.sort_by_key(|r| dfs.pre_number(r.entries[0]));
+
+        regions
+    }
+
+    /// Blocks in the region, in index order
+    pub fn blocks(&self) -> impl Iterator<Item = usize> + '_ {
+        self.blocks.iter().copied()
+    }
+
+    pub fn contains(&self, block: usize) -> bool {
+        self.blocks.contains(&block)
+    }
+
+    /// Blocks of the region that can be reached from outside it, in the order a depth first search reaches them
+    pub fn entries(&self) -> &[usize] {
+        &self.entries
+    }
+
+    /// Labels of the blocks in the region, see [crate::BasicBlock::label_or_default]
+    pub fn labels(&self, cfg: &CFG) -> Vec<String> {
+        self.blocks()
+            .map(|b| cfg.get(b).label_or_default().to_owned())
+            .collect()
+    }
+}
+
+/// Strongly connected components of the subgraph induced by `nodes`, using Kosaraju's algorithm
+fn components(
+    nodes: &BTreeSet<usize>,
+    succs: &[Vec<usize>],
+    preds: &[Vec<usize>],
+) -> Vec<Vec<usize>> {
+    // Order the nodes by when their search finishes
+    let mut visited = BTreeSet::new();
+    let mut finished = vec![];
+    for &root in nodes {
+        if !visited.insert(root) {
+            continue;
+        }
+
+        let mut stack = vec![(root, 0)];
+        while let Some((node, next)) = stack.last_mut() {
+            let node = *node;
+            match succs[node].get(*next) {
+                Some(&succ) => {
+                    *next += 1;
+                    if nodes.contains(&succ) && visited.insert(succ) {
+                        stack.push((succ, 0));
+                    }
+                }
+                None => {
+                    finished.push(node);
+                    stack.pop();
+                }
+            }
+        }
+    }
+
+    // Searching backwards from the last to finish finds one component at a time
+    let mut assigned = BTreeSet::new();
+    let mut components = vec![];
+    for &root in finished.iter().rev() {
+        if !assigned.insert(root) {
+            continue;
+        }
+
+        let mut component = vec![];
+        let mut stack = vec![root];
+        while let Some(node) = stack.pop() {
+            component.push(node);
+            for &pred in &preds[node] {
+                if nodes.contains(&pred) && assigned.insert(pred) {
+                    stack.push(pred);
+                }
+            }
+        }
+        components.push(component);
+    }
+
+    components
+}